@@ -0,0 +1,90 @@
+//! Compares looking for overlapping entities with a nested loop over every pair of entities
+//! against querying a `SpatialGrid`, the way `WinLoseChecker` used to and now does.
+//!
+//! Run with `cargo bench --bench spatial_grid`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use sdl2::rect::Rect;
+use specs::{Builder, Entity, World, WorldExt};
+
+use learning_game_in_rust::spatial_grid::SpatialGrid;
+
+/// The size of the square area entities are scattered in
+const WORLD_SIZE: i32 = 8000;
+
+fn scattered_entities(count: usize) -> Vec<(Entity, Rect)> {
+    let mut world = World::new();
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..count).map(|_| {
+        let entity = world.create_entity().build();
+        let x = rng.gen_range(-WORLD_SIZE / 2..WORLD_SIZE / 2);
+        let y = rng.gen_range(-WORLD_SIZE / 2..WORLD_SIZE / 2);
+        (entity, Rect::from_center((x, y), 50, 58))
+    }).collect()
+}
+
+/// Every entity is checked against every other entity, e.g. projectiles against enemies
+fn overlapping_pairs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("overlapping_pairs");
+    for count in [100, 1_000, 5_000] {
+        let entities = scattered_entities(count);
+
+        group.bench_with_input(BenchmarkId::new("nested_loop", count), &entities, |b, entities| {
+            b.iter(|| {
+                let mut pairs = 0;
+                for (i, &(_, bounds)) in entities.iter().enumerate() {
+                    for &(_, other_bounds) in &entities[i + 1..] {
+                        if bounds.has_intersection(other_bounds) {
+                            pairs += 1;
+                        }
+                    }
+                }
+                pairs
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("spatial_grid", count), &entities, |b, entities| {
+            let mut grid = SpatialGrid::default();
+            b.iter(|| {
+                // Rebuilding is part of every update of the world, so it is measured as well
+                grid.rebuild(entities.iter().copied());
+                entities.iter()
+                    .map(|&(entity, bounds)| grid.query_rect(bounds).into_iter().filter(|&other| other != entity).count())
+                    .sum::<usize>() / 2
+            })
+        });
+    }
+    group.finish();
+}
+
+/// A single entity looking for the nearest of every other entity
+fn nearest_entity(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nearest_entity");
+    for count in [100, 1_000, 5_000] {
+        let entities = scattered_entities(count);
+        let (player, player_bounds) = entities[0];
+
+        group.bench_with_input(BenchmarkId::new("linear_scan", count), &entities, |b, entities| {
+            b.iter(|| {
+                entities.iter()
+                    .filter(|&&(entity, _)| entity != player)
+                    .min_by_key(|&&(_, bounds)| {
+                        let offset = bounds.center() - player_bounds.center();
+                        i64::from(offset.x()).pow(2) + i64::from(offset.y()).pow(2)
+                    })
+                    .map(|&(entity, _)| entity)
+            })
+        });
+
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(entities.iter().copied());
+        group.bench_with_input(BenchmarkId::new("spatial_grid", count), &grid, |b, grid| {
+            b.iter(|| grid.nearest(player_bounds.center(), WORLD_SIZE as u32 * 2, |entity| entity != player))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, overlapping_pairs, nearest_entity);
+criterion_main!(benches);
@@ -0,0 +1,159 @@
+//! Textures are referred to by `TextureHandle`s handed out by the `AssetManager` resource, which
+//! only knows the path of each texture. The textures themselves are loaded by `LoadedTextures` on
+//! the main thread, because SDL textures cannot be shared with the systems of the world.
+//!
+//! `LoadedTextures` also watches the files it loaded, so that a sprite sheet saved by an image
+//! editor shows up in the running game without restarting it.
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sdl2::{
+    image::LoadTexture,
+    render::{Texture, TextureCreator},
+    video::WindowContext,
+};
+
+/// Refers to a texture registered in the `AssetManager`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+/// Hands out a `TextureHandle` for every texture path used by the world. Registering the same path
+/// twice returns the same handle, so each image is only loaded once.
+#[derive(Debug, Default)]
+pub struct AssetManager {
+    texture_paths: Vec<PathBuf>,
+    texture_handles: HashMap<PathBuf, TextureHandle>,
+}
+
+impl AssetManager {
+    /// The handle of the texture at the given path, registering it if needed
+    pub fn texture(&mut self, path: impl AsRef<Path>) -> TextureHandle {
+        let path = path.as_ref();
+        if let Some(&handle) = self.texture_handles.get(path) {
+            return handle;
+        }
+        let handle = TextureHandle(self.texture_paths.len());
+        self.texture_paths.push(path.to_path_buf());
+        self.texture_handles.insert(path.to_path_buf(), handle);
+        handle
+    }
+
+    /// The path of the texture with the given handle
+    pub fn texture_path(&self, TextureHandle(index): TextureHandle) -> &Path {
+        &self.texture_paths[index]
+    }
+
+    /// The number of textures registered so far
+    pub fn texture_count(&self) -> usize {
+        self.texture_paths.len()
+    }
+}
+
+/// The reasons a texture can fail to load
+#[derive(Debug)]
+pub struct AssetError {
+    /// The path of the texture
+    pub path: PathBuf,
+    /// What went wrong, as reported by SDL
+    pub message: String,
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not load texture {}: {}", self.path.display(), self.message)
+    }
+}
+
+impl Error for AssetError {}
+
+/// The textures registered in an `AssetManager`, loaded with SDL
+pub struct LoadedTextures<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    textures: Vec<LoadedTexture<'a>>,
+}
+
+/// A texture along with what is needed to tell whether its file changed
+struct LoadedTexture<'a> {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// `None` while the file cannot be loaded
+    texture: Option<Texture<'a>>,
+}
+
+impl<'a> LoadedTextures<'a> {
+    /// No textures, to be loaded with the given texture creator
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        Self {
+            texture_creator,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Loads every texture registered in `assets` since the last call, and returns why some of
+    /// them could not be loaded. Those textures are not drawn until their file is modified and
+    /// they are loaded by `reload_modified`.
+    pub fn load_new(&mut self, assets: &AssetManager) -> Vec<AssetError> {
+        let mut errors = Vec::new();
+        for index in self.textures.len()..assets.texture_count() {
+            let path = assets.texture_path(TextureHandle(index)).to_path_buf();
+            let modified = modified_time(&path);
+            let texture = match self.load(&path) {
+                Ok(texture) => Some(texture),
+                Err(error) => {
+                    errors.push(error);
+                    None
+                },
+            };
+            self.textures.push(LoadedTexture {path, modified, texture});
+        }
+        errors
+    }
+
+    /// Loads again every texture whose file was modified since it was last checked, and returns
+    /// the path of each of them. A texture that fails to reload keeps its previous image, and is
+    /// only tried again once its file changes again, e.g. once the file has been saved entirely,
+    /// so that each failure is reported once.
+    pub fn reload_modified(&mut self) -> Vec<Result<PathBuf, AssetError>> {
+        let mut reloaded = Vec::new();
+        for index in 0..self.textures.len() {
+            let modified = modified_time(&self.textures[index].path);
+            if modified == self.textures[index].modified {
+                continue;
+            }
+            self.textures[index].modified = modified;
+            let path = self.textures[index].path.clone();
+            match self.load(&path) {
+                Ok(texture) => {
+                    self.textures[index].texture = Some(texture);
+                    reloaded.push(Ok(path));
+                },
+                Err(error) => reloaded.push(Err(error)),
+            }
+        }
+        reloaded
+    }
+
+    /// The texture with the given handle, if it has been loaded
+    pub fn get(&self, TextureHandle(index): TextureHandle) -> Option<&Texture<'a>> {
+        self.textures.get(index).and_then(|loaded| loaded.texture.as_ref())
+    }
+
+    fn load(&self, path: &Path) -> Result<Texture<'a>, AssetError> {
+        // Checked first because SDL_image does not say which file is missing
+        if !path.is_file() {
+            return Err(AssetError {path: path.to_path_buf(), message: "no such file".to_string()});
+        }
+        self.texture_creator.load_texture(path)
+            .map_err(|message| AssetError {path: path.to_path_buf(), message})
+    }
+}
+
+/// When the file at the given path was last modified, if that can be known
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
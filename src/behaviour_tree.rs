@@ -0,0 +1,698 @@
+//! Behaviour trees let designers compose the logic of an enemy from small nodes written in a data
+//! file, rather than picking one of the reactions built into the `AI` system. A tree is ticked from
+//! its root at every update of the world: composite nodes tick their children in order, decorators
+//! change what their child reports, and conditions and actions, the leaves of the tree, query and
+//! drive the entity through an [`Agent`]. The values a tree reads and writes by name are kept in
+//! the [`Blackboard`] of each entity, along with what its nodes were doing at the previous tick.
+//!
+//! Trees are reactive: every tick starts over from the root, so a higher priority branch of a
+//! selector takes over as soon as its conditions hold. A node that is not ticked for a tick starts
+//! over the next time it is ticked.
+//!
+//! See `assets/behaviours/sentry.ron` for an example of the format, and the `TreeHarness` of the
+//! tests of this module to check what a tree makes an enemy do without running the whole game.
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::components::Blackboard;
+
+/// What a node reports after being ticked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The node did what it had to do
+    Success,
+    /// The node could not do what it had to do
+    Failure,
+    /// The node is not done yet and must be ticked again
+    Running,
+}
+
+/// A node of a behaviour tree, as written in a behaviour tree file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Node {
+    /// Ticks its children in order until one of them does not succeed, and reports what that
+    /// child reported. Succeeds if every child succeeds.
+    Sequence(Vec<Node>),
+    /// Ticks its children in order until one of them does not fail, and reports what that child
+    /// reported. Fails if every child fails.
+    Selector(Vec<Node>),
+    /// Changes what its child reports
+    Decorator(Decorator, Box<Node>),
+    /// Succeeds when the condition holds and fails otherwise
+    Condition(Condition),
+    /// Makes the entity do something
+    Action(Action),
+}
+
+/// The ways a `Node::Decorator` changes what its child reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Decorator {
+    /// Succeeds when the child fails and fails when it succeeds
+    Invert,
+    /// Succeeds once the child is done, whether it succeeded or failed
+    Succeed,
+    /// Fails without ticking the child for the given number of milliseconds after the child
+    /// succeeds
+    Cooldown(u64),
+    /// Fails without ticking the child once the child has been running for the given number of
+    /// milliseconds
+    TimeLimit(u64),
+}
+
+/// What a `Node::Condition` checks
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Condition {
+    /// The player is within the sight radius of the enemy and nothing blocks the view
+    PlayerInSight,
+    /// The enemy has seen the player recently enough to remember where
+    PlayerRemembered,
+    /// The player is within the given number of pixels of the enemy, seen or not
+    PlayerWithin(u32),
+    /// The enemy is at its post
+    AtPost,
+    /// Holds at random with the given probability, from `0.0` to `1.0`, every time it is checked
+    Chance(f32),
+    /// The blackboard has a value with the given name
+    IsSet(String),
+    /// The blackboard has the given value with the given name
+    Equals(String, BlackboardValue),
+}
+
+/// What a `Node::Action` does
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum Action {
+    /// Follows the `IdleBehaviour` of the enemy. Never done.
+    Idle,
+    /// Walks around by randomly changing direction, like `IdleBehaviour::Wander`. Never done.
+    Wander,
+    /// Stops walking. Done at once.
+    Stand,
+    /// Walks to the target around obstacles. Done once the target is reached, or as close as the
+    /// enemy can get to it, and fails when there is no target.
+    WalkTo(Target),
+    /// Runs away from where the player was last seen. Never done, and fails when the enemy does
+    /// not remember the player.
+    Flee,
+    /// Does nothing for the given number of milliseconds
+    Wait(u64),
+    /// Writes the given value to the blackboard under the given name. Done at once.
+    Set(String, BlackboardValue),
+    /// Removes the value with the given name from the blackboard. Done at once.
+    Clear(String),
+}
+
+/// Where a `Action::WalkTo` goes
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub enum Target {
+    /// The post of the enemy
+    Post,
+    /// Where the player was last seen, while the enemy remembers it
+    Player,
+    /// The `BlackboardValue::Point` with the given name
+    Key(String),
+}
+
+/// A value of a `Blackboard`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum BlackboardValue {
+    /// A flag
+    Bool(bool),
+    /// A number, e.g. a counter
+    Number(f32),
+    /// A point of the world, in world coordinates
+    Point(i32, i32),
+}
+
+/// The entity ticked by a behaviour tree: checks the conditions and performs the actions of the
+/// tree that depend on the world. The tree handles `Condition::IsSet`, `Condition::Equals`,
+/// `Action::Wait`, `Action::Set` and `Action::Clear` itself and never passes them on.
+pub trait Agent {
+    /// Whether the condition holds
+    fn check(&mut self, condition: &Condition, blackboard: &Blackboard) -> bool;
+
+    /// Performs a single tick of the action. `running_for` is how long the action has been
+    /// running, or `None` when it starts.
+    fn act(&mut self, action: &Action, blackboard: &mut Blackboard, running_for: Option<Duration>) -> Status;
+}
+
+/// What the nodes of a behaviour tree were doing when it was last ticked for an entity
+#[derive(Debug, Default, Clone)]
+pub struct TreeState {
+    /// The sum of the time deltas the tree was ticked with
+    clock: Duration,
+    /// The `clock` at which each node still running after the last tick started, by index
+    running: HashMap<usize, Duration>,
+    /// The `clock` at which each `Decorator::Cooldown` lets its child be ticked again, by index
+    cooldowns: HashMap<usize, Duration>,
+    /// What the root reported at the last tick
+    status: Option<Status>,
+}
+
+impl TreeState {
+    /// What the root of the tree reported at the last tick, if it was ever ticked
+    pub fn status(&self) -> Option<Status> {
+        self.status
+    }
+}
+
+/// A behaviour tree ready to be ticked, usually shared by every enemy that behaves the same
+#[derive(Debug, Clone)]
+pub struct BehaviourTree {
+    /// The nodes of the tree in depth-first order, starting with the root
+    nodes: Vec<TreeNode>,
+}
+
+/// A node of a `BehaviourTree`, which refers to its children by index. Indices identify the nodes
+/// in the `TreeState` of each entity.
+#[derive(Debug, Clone)]
+enum TreeNode {
+    Sequence(Vec<usize>),
+    Selector(Vec<usize>),
+    Decorator(Decorator, usize),
+    Condition(Condition),
+    Action(Action),
+}
+
+/// The reasons a behaviour tree file can fail to load
+#[derive(Debug)]
+pub enum BehaviourTreeError {
+    /// The tree file could not be read
+    Io {
+        /// The path of the tree file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The tree file is not valid RON or does not match the tree format
+    Parse {
+        /// The path of the tree file
+        path: PathBuf,
+        /// The underlying error, including the position of the error in the file
+        source: ron::error::SpannedError,
+    },
+    /// The tree file is well-formed but describes a tree that cannot be ticked
+    Invalid {
+        /// The path of the tree file
+        path: PathBuf,
+        /// What is wrong with the tree
+        message: String,
+    },
+}
+
+impl fmt::Display for BehaviourTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BehaviourTreeError::Io {path, source} => write!(f, "Could not read behaviour tree {}: {}", path.display(), source),
+            BehaviourTreeError::Parse {path, source} => write!(f, "Could not parse behaviour tree {}: {}", path.display(), source),
+            BehaviourTreeError::Invalid {path, message} => write!(f, "Invalid behaviour tree {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for BehaviourTreeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BehaviourTreeError::Io {source, ..} => Some(source),
+            BehaviourTreeError::Parse {source, ..} => Some(source),
+            BehaviourTreeError::Invalid {..} => None,
+        }
+    }
+}
+
+impl BehaviourTree {
+    /// A tree with the given root
+    pub fn new(root: Node) -> Self {
+        let mut tree = Self {nodes: Vec::new()};
+        tree.push(root);
+        tree
+    }
+
+    /// Reads and validates the tree file at the given path
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BehaviourTreeError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|source| BehaviourTreeError::Io {path: path.to_path_buf(), source})?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses and validates a tree. The `path` is only used in error messages.
+    pub fn parse(source: &str, path: impl AsRef<Path>) -> Result<Self, BehaviourTreeError> {
+        let path = path.as_ref();
+        let root: Node = ron::from_str(source)
+            .map_err(|source| BehaviourTreeError::Parse {path: path.to_path_buf(), source})?;
+        validate(&root)
+            .map_err(|message| BehaviourTreeError::Invalid {path: path.to_path_buf(), message})?;
+        Ok(Self::new(root))
+    }
+
+    /// Ticks the tree once for an entity, after `time_delta` has elapsed since the previous tick,
+    /// and returns what the root reported
+    pub fn tick(&self, agent: &mut impl Agent, blackboard: &mut Blackboard, time_delta: Duration) -> Status {
+        // Taken out of the blackboard so that the agent can be given the blackboard meanwhile
+        let mut state = std::mem::take(&mut blackboard.tree);
+        state.clock += time_delta;
+        let mut ticker = Ticker {
+            nodes: &self.nodes,
+            agent,
+            blackboard,
+            clock: state.clock,
+            previously_running: &state.running,
+            running: HashMap::new(),
+            cooldowns: &mut state.cooldowns,
+        };
+        let status = ticker.tick(0);
+        // Nodes that were not ticked or are done start over the next time they are ticked
+        state.running = ticker.running;
+        state.status = Some(status);
+        blackboard.tree = state;
+        status
+    }
+
+    /// Adds a node and its descendants in depth-first order and returns the index of the node
+    fn push(&mut self, node: Node) -> usize {
+        let index = self.nodes.len();
+        // Reserved until the indices of the children are known
+        self.nodes.push(TreeNode::Sequence(Vec::new()));
+        self.nodes[index] = match node {
+            Node::Sequence(children) => TreeNode::Sequence(children.into_iter().map(|child| self.push(child)).collect()),
+            Node::Selector(children) => TreeNode::Selector(children.into_iter().map(|child| self.push(child)).collect()),
+            Node::Decorator(decorator, child) => TreeNode::Decorator(decorator, self.push(*child)),
+            Node::Condition(condition) => TreeNode::Condition(condition),
+            Node::Action(action) => TreeNode::Action(action),
+        };
+        index
+    }
+}
+
+/// Checks everything that the file format itself cannot express
+fn validate(node: &Node) -> Result<(), String> {
+    match node {
+        Node::Sequence(children) | Node::Selector(children) => {
+            if children.is_empty() {
+                return Err(format!("sequences and selectors must have at least one child, got {:?}", node));
+            }
+            children.iter().try_for_each(validate)
+        },
+        Node::Decorator(_, child) => validate(child),
+        &Node::Condition(Condition::Chance(probability)) if !(0.0..=1.0).contains(&probability) => {
+            Err(format!("chances must be between 0.0 and 1.0, got {}", probability))
+        },
+        Node::Condition(_) | Node::Action(_) => Ok(()),
+    }
+}
+
+/// Ticks the nodes of a tree for a single entity
+struct Ticker<'a, A: Agent> {
+    nodes: &'a [TreeNode],
+    agent: &'a mut A,
+    blackboard: &'a mut Blackboard,
+    clock: Duration,
+    previously_running: &'a HashMap<usize, Duration>,
+    /// The nodes running after this tick, and when they started
+    running: HashMap<usize, Duration>,
+    cooldowns: &'a mut HashMap<usize, Duration>,
+}
+
+impl<'a, A: Agent> Ticker<'a, A> {
+    /// Ticks the node at the given index and its children as needed
+    fn tick(&mut self, index: usize) -> Status {
+        let nodes = self.nodes;
+        let started = self.previously_running.get(&index).copied();
+        let running_for = started.map(|started| self.clock - started);
+        let status = match &nodes[index] {
+            TreeNode::Sequence(children) => children.iter()
+                .map(|&child| self.tick(child))
+                .find(|&status| status != Status::Success)
+                .unwrap_or(Status::Success),
+            TreeNode::Selector(children) => children.iter()
+                .map(|&child| self.tick(child))
+                .find(|&status| status != Status::Failure)
+                .unwrap_or(Status::Failure),
+            &TreeNode::Decorator(decorator, child) => match decorator {
+                Decorator::Invert => match self.tick(child) {
+                    Status::Success => Status::Failure,
+                    Status::Failure => Status::Success,
+                    Status::Running => Status::Running,
+                },
+                Decorator::Succeed => match self.tick(child) {
+                    Status::Running => Status::Running,
+                    Status::Success | Status::Failure => Status::Success,
+                },
+                Decorator::Cooldown(_) if self.cooldowns.get(&index).is_some_and(|&until| self.clock < until) => Status::Failure,
+                Decorator::Cooldown(cooldown_ms) => {
+                    let status = self.tick(child);
+                    if status == Status::Success {
+                        self.cooldowns.insert(index, self.clock + Duration::from_millis(cooldown_ms));
+                    }
+                    status
+                },
+                Decorator::TimeLimit(limit_ms) if running_for.is_some_and(|running_for| running_for >= Duration::from_millis(limit_ms)) => Status::Failure,
+                Decorator::TimeLimit(_) => self.tick(child),
+            },
+            TreeNode::Condition(Condition::IsSet(name)) => status_of(self.blackboard.values.contains_key(name)),
+            TreeNode::Condition(Condition::Equals(name, value)) => status_of(self.blackboard.values.get(name) == Some(value)),
+            TreeNode::Condition(condition) => status_of(self.agent.check(condition, self.blackboard)),
+            &TreeNode::Action(Action::Wait(duration_ms)) => match running_for.unwrap_or_default() >= Duration::from_millis(duration_ms) {
+                true => Status::Success,
+                false => Status::Running,
+            },
+            TreeNode::Action(Action::Set(name, value)) => {
+                self.blackboard.values.insert(name.clone(), *value);
+                Status::Success
+            },
+            TreeNode::Action(Action::Clear(name)) => {
+                self.blackboard.values.remove(name);
+                Status::Success
+            },
+            TreeNode::Action(action) => self.agent.act(action, self.blackboard, running_for),
+        };
+        if status == Status::Running {
+            self.running.insert(index, started.unwrap_or(self.clock));
+        }
+        status
+    }
+}
+
+/// Succeeds when true and fails when false
+fn status_of(holds: bool) -> Status {
+    match holds {
+        true => Status::Success,
+        false => Status::Failure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sdl2::rect::{Point, Rect};
+    use specs::{World, WorldExt, Builder, Entity, RunNow, System};
+
+    use crate::vector::Vector;
+    use crate::systems::AI;
+    use crate::pathfinding::{NavGrid, Pathfinder, PathSearch};
+    use crate::resources::{GameRng, GameTime, TimeDelta};
+    use crate::components::{Enemy, EnemyMode, IdleBehaviour, Reaction, Player, Position, Locomotion, Route, Solid, BoundingBox, Behaviour};
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    /// A small world holding a single enemy driven by a behaviour tree, and optionally the player
+    /// and some obstacles, to check what a tree makes the enemy do. Only the `AI` system runs:
+    /// nothing moves unless placed somewhere with `place_enemy` or `place_player`, and the
+    /// decisions of the enemy are read from its `Locomotion`, `Route` and `Blackboard`.
+    struct TreeHarness {
+        world: World,
+        world_bounds: Rect,
+        enemy: Entity,
+        player: Option<Entity>,
+    }
+
+    impl TreeHarness {
+        /// A world of the given bounds without obstacles nor player, with the enemy standing at its
+        /// post
+        fn new(tree: BehaviourTree, enemy: Enemy, world_bounds: Rect) -> Self {
+            let mut world = World::new();
+            System::setup(&mut AI, &mut world);
+            // Read to build the navigation grid of the `Pathfinder`
+            world.register::<Solid>();
+            world.register::<BoundingBox>();
+            world.insert(GameRng::from_seed(0));
+            world.insert(GameTime::default());
+            world.insert(TimeDelta::default());
+            world.insert(Pathfinder::new(NavGrid::new(world_bounds, Vec::new()), PathSearch::default()));
+
+            let post = enemy.post;
+            let enemy = world.create_entity()
+                .with(enemy)
+                .with(Position(Vector::from_point(post)))
+                .with(Locomotion::instant(0.0))
+                .with(Behaviour(Arc::new(tree)))
+                .with(Blackboard::default())
+                .build();
+            Self {world, world_bounds, enemy, player: None}
+        }
+
+        /// Adds a static obstacle that blocks the way and the view of the enemy
+        fn with_obstacle(mut self, bounds: Rect) -> Self {
+            self.world.create_entity()
+                .with(Solid)
+                .with(BoundingBox(bounds))
+                .build();
+            let grid = NavGrid::from_world(&self.world, self.world_bounds);
+            self.world.write_resource::<Pathfinder>().set_grid(grid);
+            self
+        }
+
+        /// Adds the player at the given position
+        fn with_player(mut self, position: Point) -> Self {
+            self.place_player(position);
+            self
+        }
+
+        /// Moves the player to the given position, adding it to the world if needed
+        fn place_player(&mut self, position: Point) {
+            let player = *self.player.get_or_insert_with(|| self.world.create_entity().with(Player).build());
+            self.world.write_storage().insert(player, Position(Vector::from_point(position)))
+                .expect("bug: the player should be alive");
+        }
+
+        /// Removes the player from the world, e.g. to see what the enemy does once it forgets them
+        fn remove_player(&mut self) {
+            if let Some(player) = self.player.take() {
+                self.world.delete_entity(player)
+                    .expect("bug: the player should be alive");
+            }
+        }
+
+        /// Moves the enemy to the given position
+        fn place_enemy(&mut self, position: Point) {
+            self.world.write_storage().insert(self.enemy, Position(Vector::from_point(position)))
+                .expect("bug: the enemy should be alive");
+        }
+
+        /// Runs the `AI` system once after `time_delta` has elapsed, and returns what the root of
+        /// the tree reported
+        fn tick(&mut self, time_delta: Duration) -> Status {
+            *self.world.write_resource() = TimeDelta(time_delta);
+            self.world.write_resource::<GameTime>().0 += time_delta;
+            AI.run_now(&self.world);
+            self.world.maintain();
+            self.world.read_storage::<Blackboard>().get(self.enemy)
+                .and_then(|blackboard| blackboard.tree.status())
+                .expect("bug: the tree should have been ticked")
+        }
+
+        /// The heading the enemy was given, see `Locomotion::heading`. Zero while it stands still
+        /// or follows a route, which the `RouteFollowing` system turns into a heading.
+        fn heading(&self) -> Vector {
+            self.world.read_storage::<Locomotion>().get(self.enemy)
+                .expect("bug: the enemy should have a locomotion")
+                .heading
+        }
+
+        /// Where the enemy is walking to, if it follows a `Route`
+        fn destination(&self) -> Option<Point> {
+            self.world.read_storage::<Route>().get(self.enemy).map(|route| route.destination)
+        }
+
+        /// The value of the blackboard of the enemy with the given name
+        fn value(&self, name: &str) -> Option<BlackboardValue> {
+            self.world.read_storage::<Blackboard>().get(self.enemy)
+                .and_then(|blackboard| blackboard.values.get(name).copied())
+        }
+    }
+
+    /// An agent for which conditions hold when they are listed in `holding`, and actions report
+    /// what `statuses` says, succeeding by default
+    #[derive(Default)]
+    struct MockAgent {
+        holding: Vec<Condition>,
+        statuses: Vec<(Action, Status)>,
+        /// The actions performed, in order, and whether each of them was starting
+        performed: Vec<(Action, bool)>,
+    }
+
+    impl Agent for MockAgent {
+        fn check(&mut self, condition: &Condition, _blackboard: &Blackboard) -> bool {
+            self.holding.contains(condition)
+        }
+
+        fn act(&mut self, action: &Action, _blackboard: &mut Blackboard, running_for: Option<Duration>) -> Status {
+            self.performed.push((action.clone(), running_for.is_none()));
+            self.statuses.iter()
+                .find(|(other, _)| other == action)
+                .map_or(Status::Success, |&(_, status)| status)
+        }
+    }
+
+    fn parse_tree(source: &str) -> BehaviourTree {
+        BehaviourTree::parse(source, "test.ron").unwrap()
+    }
+
+    fn sentry(post: Point) -> Enemy {
+        Enemy {
+            idle: IdleBehaviour::Guard,
+            reaction: Reaction::Ignore,
+            sight_radius: 300,
+            memory: Duration::from_millis(1000),
+            post,
+            mode: EnemyMode::Idle,
+            last_seen: None,
+            next_waypoint: 0,
+            direction_changed: Duration::ZERO,
+            direction_change_delay: Duration::from_millis(200),
+        }
+    }
+
+    #[test]
+    fn sequence_stops_at_the_first_child_that_does_not_succeed() {
+        let tree = parse_tree("Sequence([Action(Stand), Condition(AtPost), Action(Wander)])");
+        let mut agent = MockAgent::default();
+        let mut blackboard = Blackboard::default();
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Failure);
+        assert_eq!(agent.performed, vec![(Action::Stand, true)]);
+
+        agent.holding.push(Condition::AtPost);
+        agent.performed.clear();
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Success);
+        assert_eq!(agent.performed, vec![(Action::Stand, true), (Action::Wander, true)]);
+    }
+
+    #[test]
+    fn selector_stops_at_the_first_child_that_does_not_fail() {
+        let tree = parse_tree("Selector([Condition(PlayerInSight), Action(Flee), Action(Wander)])");
+        let mut agent = MockAgent {statuses: vec![(Action::Flee, Status::Failure), (Action::Wander, Status::Running)], ..MockAgent::default()};
+        let mut blackboard = Blackboard::default();
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Running);
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Running);
+        // Wander was still running at the second tick
+        assert_eq!(agent.performed, vec![(Action::Flee, true), (Action::Wander, true), (Action::Flee, true), (Action::Wander, false)]);
+
+        agent.holding.push(Condition::PlayerInSight);
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Success);
+
+        let tree = parse_tree("Selector([Condition(PlayerInSight), Condition(AtPost)])");
+        assert_eq!(tree.tick(&mut MockAgent::default(), &mut Blackboard::default(), TICK), Status::Failure);
+    }
+
+    #[test]
+    fn invert_and_succeed_change_what_the_child_reports() {
+        let mut agent = MockAgent {statuses: vec![(Action::Wander, Status::Running)], ..MockAgent::default()};
+        let mut blackboard = Blackboard::default();
+        for (source, expected) in [
+            ("Decorator(Invert, Condition(AtPost))", Status::Success),
+            ("Decorator(Invert, Action(Stand))", Status::Failure),
+            ("Decorator(Invert, Action(Wander))", Status::Running),
+            ("Decorator(Succeed, Condition(AtPost))", Status::Success),
+            ("Decorator(Succeed, Action(Stand))", Status::Success),
+            ("Decorator(Succeed, Action(Wander))", Status::Running),
+        ] {
+            assert_eq!(parse_tree(source).tick(&mut agent, &mut blackboard, TICK), expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn cooldown_fails_until_it_expires() {
+        let tree = parse_tree("Decorator(Cooldown(250), Action(Stand))");
+        let mut agent = MockAgent::default();
+        let mut blackboard = Blackboard::default();
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Success);
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Failure);
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Failure);
+        assert_eq!(tree.tick(&mut agent, &mut blackboard, TICK), Status::Success);
+        // The child was not ticked while cooling down
+        assert_eq!(agent.performed.len(), 2);
+    }
+
+    #[test]
+    fn time_limit_fails_once_the_child_ran_too_long() {
+        let tree = parse_tree("Decorator(TimeLimit(250), Action(Wait(1000)))");
+        let mut blackboard = Blackboard::default();
+        let statuses: Vec<_> = (0..4).map(|_| tree.tick(&mut MockAgent::default(), &mut blackboard, TICK)).collect();
+        assert_eq!(statuses, vec![Status::Running, Status::Running, Status::Running, Status::Failure]);
+        // The limit starts over once the child is not running anymore
+        assert_eq!(tree.tick(&mut MockAgent::default(), &mut blackboard, TICK), Status::Running);
+    }
+
+    #[test]
+    fn wait_only_counts_the_time_the_tree_was_ticked_with() {
+        let tree = parse_tree("Action(Wait(150))");
+        let mut blackboard = Blackboard::default();
+        assert_eq!(tree.tick(&mut MockAgent::default(), &mut blackboard, TICK), Status::Running);
+        // Paused
+        for _ in 0..10 {
+            assert_eq!(tree.tick(&mut MockAgent::default(), &mut blackboard, Duration::ZERO), Status::Running);
+        }
+        // Slowed down to half speed, from the tick the wait started at
+        for _ in 0..2 {
+            assert_eq!(tree.tick(&mut MockAgent::default(), &mut blackboard, TICK / 2), Status::Running);
+        }
+        assert_eq!(tree.tick(&mut MockAgent::default(), &mut blackboard, TICK / 2), Status::Success);
+    }
+
+    #[test]
+    fn validate_rejects_empty_composites_and_impossible_chances() {
+        for source in ["Sequence([])", "Selector([Action(Stand), Selector([])])", "Condition(Chance(1.5))", "Decorator(Invert, Condition(Chance(-0.1)))"] {
+            let error = BehaviourTree::parse(source, "test.ron").unwrap_err();
+            assert!(matches!(error, BehaviourTreeError::Invalid {..}), "{} gave {}", source, error);
+        }
+        assert!(matches!(BehaviourTree::parse("Sequence([Action(Dance)])", "test.ron"), Err(BehaviourTreeError::Parse {..})));
+    }
+
+    #[test]
+    fn sentry_chases_searches_and_goes_back_to_its_post() {
+        let tree = BehaviourTree::load("assets/behaviours/sentry.ron").unwrap();
+        let post = Point::new(0, 0);
+        let mut harness = TreeHarness::new(tree, sentry(post), Rect::new(-400, -400, 800, 800))
+            .with_player(Point::new(200, 0));
+
+        // Seen: chases the player and remembers to search for them
+        assert_eq!(harness.tick(TICK), Status::Running);
+        assert_eq!(harness.destination(), Some(Point::new(200, 0)));
+        assert_eq!(harness.value("searching"), Some(BlackboardValue::Bool(true)));
+
+        // Reaches the place the player was last seen after they left, and stands there
+        harness.remove_player();
+        harness.place_enemy(Point::new(200, 0));
+        assert_eq!(harness.tick(TICK), Status::Success);
+        assert_eq!(harness.destination(), None);
+        assert_eq!(harness.heading(), Vector::ZERO);
+
+        // Forgets the player, waits for a while and stops searching
+        for _ in 0..30 {
+            harness.tick(TICK);
+        }
+        assert_eq!(harness.value("searching"), None);
+
+        // Strolls around, then walks back to its post once the stroll is over
+        for _ in 0..20 {
+            harness.tick(TICK);
+        }
+        assert_eq!(harness.tick(TICK), Status::Running);
+        assert_eq!(harness.destination(), Some(post));
+    }
+
+    #[test]
+    fn sentry_does_not_see_through_obstacles() {
+        let tree = BehaviourTree::load("assets/behaviours/sentry.ron").unwrap();
+        let mut harness = TreeHarness::new(tree, sentry(Point::new(0, 0)), Rect::new(-400, -400, 800, 800))
+            .with_obstacle(Rect::new(80, -100, 40, 200))
+            .with_player(Point::new(200, 0));
+        assert_eq!(harness.tick(TICK), Status::Running);
+        assert_eq!(harness.value("searching"), None);
+        assert_eq!(harness.destination(), None);
+
+        harness.place_player(Point::new(0, 200));
+        harness.tick(TICK);
+        assert_eq!(harness.destination(), Some(Point::new(0, 200)));
+    }
+}
@@ -0,0 +1,222 @@
+//! The camera decides which part of the world is visible on the screen. It follows a target
+//! entity (the player by default) with some smoothing and a dead-zone, stays within the bounds of
+//! the world and can be shaken. The renderer uses it to convert world coordinates to screen
+//! coordinates, and input handling can use it to convert screen coordinates back to the world.
+
+use std::time::Duration;
+
+use sdl2::rect::{Point, Rect};
+use specs::Entity;
+
+use crate::vector::Vector;
+
+/// The size of the screen assumed until the renderer reports the actual size of the window
+const DEFAULT_VIEWPORT: (u32, u32) = (900, 900);
+
+/// How the world is viewed. Updated at every fixed timestep by the `CameraFollow` system.
+#[derive(Debug, Clone)]
+pub struct Camera {
+    /// The entity followed by the camera. When `None`, the player is followed.
+    pub target: Option<Entity>,
+    /// How quickly the camera catches up with its target. Higher is faster; `0.0` never moves
+    /// and `f32::INFINITY` snaps to the target immediately.
+    pub smoothing: f32,
+    /// The width and height of the area around the center of the camera in which the target can
+    /// move without the camera following it, in world coordinates
+    pub dead_zone: (u32, u32),
+    /// The scale of the world on the screen. `2.0` shows everything twice as big.
+    pub zoom: f32,
+    /// The width and height of the screen area the world is drawn in, in pixels
+    pub viewport: (u32, u32),
+    /// The center of the camera in world coordinates
+    position: (f32, f32),
+    /// The center of the camera before the most recent update, for interpolated rendering
+    previous_position: (f32, f32),
+    /// The entity followed during the most recent update
+    followed: Option<Entity>,
+    /// The screen-shake currently applied, if any
+    shake: Option<Shake>,
+}
+
+/// A screen-shake fading out over its duration
+#[derive(Debug, Clone)]
+struct Shake {
+    /// The maximum offset of the camera, in pixels
+    intensity: f32,
+    /// How long the shake lasts in total
+    duration: Duration,
+    /// How long the shake has been going on for
+    elapsed: Duration,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            target: None,
+            smoothing: 8.0,
+            dead_zone: (64, 48),
+            zoom: 1.0,
+            viewport: DEFAULT_VIEWPORT,
+            position: (0.0, 0.0),
+            previous_position: (0.0, 0.0),
+            followed: None,
+            shake: None,
+        }
+    }
+}
+
+impl Camera {
+    /// The center of the camera in world coordinates, as of the most recent update
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+
+    /// Moves the camera to the given center immediately, without any smoothing
+    pub fn jump_to(&mut self, position: (f32, f32)) {
+        self.position = position;
+        self.previous_position = position;
+    }
+
+    /// Shakes the camera by up to `intensity` pixels, fading out over `duration`. Replaces any
+    /// shake already going on. Shakes without any duration are ignored.
+    pub fn shake(&mut self, intensity: f32, duration: Duration) {
+        if duration.is_zero() {
+            return;
+        }
+        self.shake = Some(Shake {intensity, duration, elapsed: Duration::ZERO});
+    }
+
+    /// Moves the camera towards the position of the followed entity over `time_delta`, keeping
+    /// everything the camera shows within `world_bounds`. The camera cuts to a newly followed
+    /// entity instead of panning over to it.
+    pub fn update(&mut self, followed: Option<(Entity, Point)>, world_bounds: Rect, time_delta: Duration) {
+        self.previous_position = self.position;
+        if let Some((entity, target)) = followed {
+            if self.followed != Some(entity) {
+                self.jump_to((target.x() as f32, target.y() as f32));
+            }
+        }
+        self.followed = followed.map(|(entity, _)| entity);
+        let (mut x, mut y) = self.position;
+
+        if let Some((_, target)) = followed {
+            // Only follow the target once it leaves the dead-zone, and then only far enough to
+            // bring it back to the edge of the dead-zone
+            let follow = |position: f32, target: f32, dead_zone: u32| {
+                let half_dead_zone = dead_zone as f32 / 2.0;
+                if target > position + half_dead_zone {
+                    target - half_dead_zone
+                } else if target < position - half_dead_zone {
+                    target + half_dead_zone
+                } else {
+                    position
+                }
+            };
+            let desired_x = follow(x, target.x() as f32, self.dead_zone.0);
+            let desired_y = follow(y, target.y() as f32, self.dead_zone.1);
+
+            // Framerate independent exponential smoothing
+            let t = 1.0 - (-self.smoothing * time_delta.as_secs_f32()).exp();
+            x += (desired_x - x) * t;
+            y += (desired_y - y) * t;
+        }
+
+        let (view_width, view_height) = self.view_size();
+        let clamp = |position: f32, view_size: f32, min: i32, max: i32| {
+            let (min, max) = (min as f32 + view_size / 2.0, max as f32 - view_size / 2.0);
+            if min > max {
+                // The world is smaller than the view: keep it centered
+                (min + max) / 2.0
+            } else {
+                position.clamp(min, max)
+            }
+        };
+        x = clamp(x, view_width, world_bounds.left(), world_bounds.right());
+        y = clamp(y, view_height, world_bounds.top(), world_bounds.bottom());
+        self.position = (x, y);
+
+        if let Some(shake) = &mut self.shake {
+            shake.elapsed += time_delta;
+            if shake.elapsed >= shake.duration {
+                self.shake = None;
+            }
+        }
+    }
+
+    /// The width and height of the part of the world visible on the screen
+    pub fn view_size(&self) -> (f32, f32) {
+        let (width, height) = self.viewport;
+        (width as f32 / self.zoom, height as f32 / self.zoom)
+    }
+
+    /// The center of the camera `alpha` of the way between the previous and the most recent
+    /// update, including any screen-shake
+    fn view_center(&self, alpha: f64) -> (f32, f32) {
+        let alpha = alpha as f32;
+        let (previous_x, previous_y) = self.previous_position;
+        let (x, y) = self.position;
+        let (shake_x, shake_y) = self.shake_offset();
+        (
+            previous_x + (x - previous_x) * alpha + shake_x / self.zoom,
+            previous_y + (y - previous_y) * alpha + shake_y / self.zoom,
+        )
+    }
+
+    /// The current offset of the screen-shake, in pixels
+    fn shake_offset(&self) -> (f32, f32) {
+        match &self.shake {
+            Some(Shake {intensity, duration, elapsed}) => {
+                let strength = intensity * (1.0 - elapsed.as_secs_f32() / duration.as_secs_f32());
+                // Two unrelated frequencies give a jittery motion without needing randomness,
+                // which keeps the simulation deterministic
+                let t = elapsed.as_secs_f32();
+                (strength * (t * 97.0).sin(), strength * (t * 83.0).cos())
+            },
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Converts a point in world coordinates to screen coordinates. `alpha` is the
+    /// `InterpolationAlpha` of the frame being drawn.
+    pub fn world_to_screen(&self, point: Point, alpha: f64) -> Point {
+        self.world_position_to_screen(Vector::from_point(point), alpha)
+    }
+
+    /// Converts a position in world coordinates, which may lie between two pixels, to the closest
+    /// point in screen coordinates
+    pub fn world_position_to_screen(&self, position: Vector, alpha: f64) -> Point {
+        let (center_x, center_y) = self.view_center(alpha);
+        let (width, height) = self.viewport;
+        Point::new(
+            ((position.x - center_x) * self.zoom + width as f32 / 2.0).round() as i32,
+            ((position.y - center_y) * self.zoom + height as f32 / 2.0).round() as i32,
+        )
+    }
+
+    /// Converts a point in screen coordinates (e.g. the position of the mouse) to world
+    /// coordinates. `alpha` is the `InterpolationAlpha` of the frame the point was seen in.
+    pub fn screen_to_world(&self, point: Point, alpha: f64) -> Point {
+        let (center_x, center_y) = self.view_center(alpha);
+        let (width, height) = self.viewport;
+        Point::new(
+            ((point.x() as f32 - width as f32 / 2.0) / self.zoom + center_x).round() as i32,
+            ((point.y() as f32 - height as f32 / 2.0) / self.zoom + center_y).round() as i32,
+        )
+    }
+
+    /// Converts a rectangle in world coordinates to screen coordinates, scaling its size by the
+    /// zoom of the camera
+    pub fn world_rect_to_screen(&self, rect: Rect, alpha: f64) -> Rect {
+        self.world_area_to_screen(Vector::from_point(rect.center()), rect.width(), rect.height(), alpha)
+    }
+
+    /// Converts a rectangle of the given size centered on a position in world coordinates to
+    /// screen coordinates. Unlike a `Rect`, the center may lie between two pixels.
+    pub fn world_area_to_screen(&self, center: Vector, width: u32, height: u32, alpha: f64) -> Rect {
+        Rect::from_center(
+            self.world_position_to_screen(center, alpha),
+            (width as f32 * self.zoom).round() as u32,
+            (height as f32 * self.zoom).round() as u32,
+        )
+    }
+}
@@ -1,10 +1,15 @@
 use std::sync::Arc;
-use std::time::{Instant, Duration};
+use std::collections::HashMap;
+use std::time::Duration;
 
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
+use serde::Deserialize;
 use specs::{Component, VecStorage, NullStorage};
 
 use crate::direction::Direction;
+use crate::vector::Vector;
+use crate::assets::TextureHandle;
+use crate::behaviour_tree::{BehaviourTree, BlackboardValue, TreeState};
 
 /// The position and dimensions of an entity in world coordinates
 ///
@@ -13,23 +18,82 @@ use crate::direction::Direction;
 #[storage(VecStorage)]
 pub struct BoundingBox(pub Rect);
 
-/// Allows an entity to move with the given speed in the given direction
-#[derive(Component, Debug, Clone)]
+/// The exact position of the center of a moving entity in world coordinates. Entities move by
+/// fractions of a pixel, so this is where the `Movement` system keeps track of them, while their
+/// `BoundingBox` is centered on this position rounded to whole pixels.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
 #[storage(VecStorage)]
-pub struct Velocity {
-    /// The speed of the entity's movement
-    pub speed: i32,
-    /// The direction of the entity's movement
-    pub direction: Direction,
+pub struct Position(pub Vector);
+
+impl Position {
+    /// The position of the center of the given bounds
+    pub fn of(bounds: Rect) -> Self {
+        Position(Vector::from_point(bounds.center()))
+    }
+}
+
+/// The `Position` of an entity before the most recent update of the world. Used by the renderer to
+/// interpolate the position of the entity between two updates without rounding it to whole pixels.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[storage(VecStorage)]
+pub struct PreviousPosition(pub Vector);
+
+/// How fast and in which direction an entity is moving, in pixels per second along each axis.
+/// Entities need a `Position` to move.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+#[storage(VecStorage)]
+pub struct Velocity(pub Vector);
+
+/// Makes an entity move by itself. Other systems steer the entity by changing its `heading`, and
+/// the `Movement` system speeds its `Velocity` up or slows it down towards that heading.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+#[storage(VecStorage)]
+pub struct Locomotion {
+    /// The direction the entity is trying to move in. Its length, from zero to one, is the
+    /// fraction of `max_speed` the entity is trying to reach, e.g. for an analog stick pushed
+    /// half way. Longer headings, like holding two arrow keys, count as a length of one.
+    pub heading: Vector,
+    /// The fastest the entity can move, in pixels per second
+    pub max_speed: f32,
+    /// How fast the entity speeds up or turns towards its heading, in pixels per second squared.
+    /// Infinite to reach any speed at once.
+    pub acceleration: f32,
+    /// How fast the entity slows down once it has no heading, in pixels per second squared.
+    /// Infinite to stop at once.
+    pub friction: f32,
+}
+
+impl Locomotion {
+    /// An entity standing still that reaches and leaves the given speed at once
+    pub fn instant(max_speed: f32) -> Self {
+        Self {
+            heading: Vector::ZERO,
+            max_speed,
+            acceleration: f32::INFINITY,
+            friction: f32::INFINITY,
+        }
+    }
+
+    /// The velocity of an entity moving with the given velocity once it has been steered towards
+    /// its heading for the given number of seconds. Nothing changes while no time passes, e.g.
+    /// when the game runs at a time scale of 0.
+    pub fn steer(&self, velocity: Vector, seconds: f32) -> Vector {
+        if seconds <= 0.0 {
+            return velocity;
+        }
+        let target = self.heading.clamp_length(1.0) * self.max_speed;
+        let rate = if target == Vector::ZERO { self.friction } else { self.acceleration };
+        velocity.move_towards(target, rate * seconds).clamp_length(self.max_speed)
+    }
 }
 
 /// The sprite to render for a given entity. The entity must also have a
-/// `Position` component in order for it to be drawn on the screen.
+/// `BoundingBox` component in order for it to be drawn on the screen.
 #[derive(Component, Debug, Clone, PartialEq, Eq)]
 #[storage(VecStorage)]
 pub struct Sprite {
     /// The texture containing the spritesheet to copy sprites from
-    pub texture_id: usize,
+    pub texture: TextureHandle,
     /// The region of the spritesheet to copy
     pub region: Rect,
 }
@@ -44,10 +108,80 @@ pub struct Animation {
     pub frames: Arc<Vec<Frame>>,
     /// The current animation frame
     pub current_frame: usize,
-    /// The amount of time elapsed since the animation frame changed
-    pub frame_timer: Instant,
+    /// The `GameTime` at which the current frame started
+    pub frame_started: Duration,
+    /// What happens after the last frame
+    pub loop_mode: LoopMode,
+    /// Whether a `LoopMode::PingPong` animation is currently going from the last frame back to
+    /// the first one
+    pub playing_backwards: bool,
+}
+
+/// What an `Animation` does once it reaches its last frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LoopMode {
+    /// Starts again from the first frame
+    #[default]
+    Loop,
+    /// Stays on the last frame
+    Once,
+    /// Plays the frames backwards down to the first one, then forwards again, and so on
+    PingPong,
 }
 
+impl Animation {
+    /// An animation starting at its first frame
+    pub fn new(frames: Vec<Frame>, loop_mode: LoopMode) -> Self {
+        assert!(!frames.is_empty(), "an animation must have at least one frame");
+        Self {
+            frames: Arc::new(frames),
+            current_frame: 0,
+            frame_started: Duration::ZERO,
+            loop_mode,
+            playing_backwards: false,
+        }
+    }
+
+    /// Goes back to the first frame, starting at the given `GameTime`
+    pub fn restart(&mut self, now: Duration) {
+        self.current_frame = 0;
+        self.frame_started = now;
+        self.playing_backwards = false;
+    }
+
+    /// Whether a `LoopMode::Once` animation has reached its last frame. Looping animations never
+    /// finish.
+    pub fn is_finished(&self) -> bool {
+        self.loop_mode == LoopMode::Once && self.current_frame == self.frames.len() - 1
+    }
+
+    /// Moves on to the frame after the current one according to the `LoopMode` of the animation.
+    /// Returns `false` if the current frame did not change.
+    pub fn advance(&mut self) -> bool {
+        let last_frame = self.frames.len() - 1;
+        let next_frame = match self.loop_mode {
+            LoopMode::Loop => (self.current_frame + 1) % self.frames.len(),
+            LoopMode::Once => (self.current_frame + 1).min(last_frame),
+            LoopMode::PingPong => {
+                if last_frame == 0 {
+                    0
+                } else {
+                    if self.current_frame == last_frame {
+                        self.playing_backwards = true;
+                    } else if self.current_frame == 0 {
+                        self.playing_backwards = false;
+                    }
+                    if self.playing_backwards { self.current_frame - 1 } else { self.current_frame + 1 }
+                }
+            },
+        };
+        let changed = next_frame != self.current_frame;
+        self.current_frame = next_frame;
+        changed
+    }
+}
+
+/// A single frame of an `Animation`
 #[derive(Debug, PartialEq, Eq)]
 pub struct Frame {
     /// The sprite to render for this frame
@@ -55,24 +189,34 @@ pub struct Frame {
     /// The duration of the animation frame. The next frame will begin once this amount of time has
     /// elapsed.
     pub duration: Duration,
+    /// The names of the `AnimationEvent`s sent when this frame starts, e.g. `"footstep"`
+    pub events: Vec<String>,
 }
 
-/// Causes an entity's `Animation` component to be updated based on the direction in
-/// its `Velocity` component.
-#[derive(Component, Debug, Clone)]
-#[storage(VecStorage)]
-pub struct MovementAnimations {
-    /// The animation for when an entity is moving in the "up" direction
-    pub walking_up: Animation,
-    /// The animation for when an entity is moving in the "down" direction
-    pub walking_down: Animation,
-    /// The animation for when an entity is moving in the "left" direction
-    pub walking_left: Animation,
-    /// The animation for when an entity is moving in the "right" direction
-    pub walking_right: Animation,
-}
-
-impl MovementAnimations {
+/// One animation for each direction an entity can face
+#[derive(Debug, Clone)]
+pub struct DirectionalAnimations {
+    /// The animation for when an entity is facing up
+    pub up: Animation,
+    /// The animation for when an entity is facing down
+    pub down: Animation,
+    /// The animation for when an entity is facing left
+    pub left: Animation,
+    /// The animation for when an entity is facing right
+    pub right: Animation,
+}
+
+impl DirectionalAnimations {
+    /// The same animation whatever the direction the entity faces
+    pub fn same(animation: Animation) -> Self {
+        Self {
+            up: animation.clone(),
+            down: animation.clone(),
+            left: animation.clone(),
+            right: animation,
+        }
+    }
+
     /// Generates a set of movement animations based on the standard convention for spritesheets
     /// in this project. Expects 4 rows of `frames_length` frames each with the animations ordered:
     /// walking down, walking left, walking right, walking up.
@@ -80,67 +224,301 @@ impl MovementAnimations {
     /// The `top_left_frame` parameter provides the offset in the spritesheet as well as the
     /// width and height (in pixels) of each frame. The `step_delay` is the duration of each frame.
     pub fn standard_walking_animations(
-        texture_id: usize,
+        texture: TextureHandle,
         top_left_frame: Rect,
         frames_length: usize,
         step_delay: Duration,
     ) -> Self {
-        let animation = |row| Animation {
-            frames: Arc::new((0..frames_length as i32).map(|frame| Frame {
-                sprite: Sprite {
-                    texture_id,
-                    region: {
-                        let mut frame_region = top_left_frame.clone();
-                        frame_region.offset(
-                            frame * top_left_frame.width() as i32,
-                            row * top_left_frame.height() as i32,
-                        );
-                        frame_region
-                    },
+        let animation = |row| Animation::new((0..frames_length as i32).map(|frame| Frame {
+            sprite: Sprite {
+                texture,
+                region: {
+                    let mut frame_region = top_left_frame;
+                    frame_region.offset(
+                        frame * top_left_frame.width() as i32,
+                        row * top_left_frame.height() as i32,
+                    );
+                    frame_region
                 },
-                duration: step_delay,
-            }).collect()),
-            current_frame: 0,
-            frame_timer: Instant::now(),
-        };
+            },
+            duration: step_delay,
+            events: Vec::new(),
+        }).collect(), LoopMode::Loop);
 
         Self {
-            walking_up: animation(3),
-            walking_down: animation(0),
-            walking_left: animation(1),
-            walking_right: animation(2),
+            up: animation(3),
+            down: animation(0),
+            left: animation(1),
+            right: animation(2),
         }
     }
 
+    /// The animation to play when facing the given direction
     pub fn animation_for(&self, direction: Direction) -> &Animation {
         match direction {
-            Direction::Up => &self.walking_up,
-            Direction::Down => &self.walking_down,
-            Direction::Left => &self.walking_left,
-            Direction::Right => &self.walking_right,
+            Direction::Up => &self.up,
+            Direction::Down => &self.down,
+            Direction::Left => &self.left,
+            Direction::Right => &self.right,
+        }
+    }
+}
+
+/// The states of an `AnimationStateMachine`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum AnimationState {
+    /// Standing still
+    Idle,
+    /// Moving according to the `Velocity` of the entity
+    Walk,
+    /// Attacking, until the animation has played once
+    Attack,
+    /// Dead, for good
+    Death,
+    /// Celebrating the victory, for good
+    Victory,
+}
+
+impl AnimationState {
+    /// Whether the state ends once its animation has played once, which requires animations
+    /// played with `LoopMode::Once`
+    pub fn plays_once(self) -> bool {
+        self == AnimationState::Attack
+    }
+}
+
+/// Gameplay conditions that take an `AnimationStateMachine` out of its idle and walking states.
+/// Set by other systems, in order of priority: death first, then victory, then attacks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFlags {
+    /// Plays the attack animation. Set by the `AI` when an enemy reaches the player, and cleared
+    /// by the `Animator` once the animation has played once.
+    pub attacking: bool,
+    /// Plays the death animation
+    pub dead: bool,
+    /// Plays the victory animation
+    pub won: bool,
+}
+
+/// Picks the `Animation` of an entity from the state it is in. The `Animator` system moves
+/// between the idle and walking states based on the `Velocity` of the entity and to the other
+/// states based on its `AnimationFlags`, and plays the animation of the current state for the
+/// direction the entity is facing.
+///
+/// A state without animations is skipped, except for the idle state which shows the first frame
+/// of the walking animation.
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct AnimationStateMachine {
+    /// The animations of each state
+    pub animations: HashMap<AnimationState, DirectionalAnimations>,
+    /// The conditions deciding which state the entity should be in
+    pub flags: AnimationFlags,
+    /// The state whose animation is being played
+    pub state: AnimationState,
+    /// The direction the entity is facing. Only changes while idle or walking.
+    pub facing: Direction,
+}
+
+impl AnimationStateMachine {
+    /// A state machine starting idle, facing the given direction
+    pub fn new(animations: HashMap<AnimationState, DirectionalAnimations>, facing: Direction) -> Self {
+        Self {
+            animations,
+            flags: AnimationFlags::default(),
+            state: AnimationState::Idle,
+            facing,
         }
     }
+
+    /// The state the entity should be in given its flags and whether it is moving
+    pub fn desired_state(&self, moving: bool) -> AnimationState {
+        let AnimationFlags {attacking, dead, won} = self.flags;
+        let candidates = [
+            (dead, AnimationState::Death),
+            (won, AnimationState::Victory),
+            (attacking, AnimationState::Attack),
+            (moving, AnimationState::Walk),
+        ];
+        candidates.into_iter()
+            .find(|&(condition, state)| condition && self.animations.contains_key(&state))
+            .map_or(AnimationState::Idle, |(_, state)| state)
+    }
+
+    /// The animation of the given state when facing the given direction, starting from its first
+    /// frame at the given `GameTime`, if the state can be shown
+    pub fn animation(&self, state: AnimationState, facing: Direction, now: Duration) -> Option<Animation> {
+        let mut animation = match (self.animations.get(&state), state) {
+            (Some(animations), _) => animations.animation_for(facing).clone(),
+            (None, AnimationState::Idle) => {
+                let walk = self.animations.get(&AnimationState::Walk)?.animation_for(facing);
+                let Frame {sprite, duration, ..} = &walk.frames[0];
+                Animation::new(vec![Frame {sprite: sprite.clone(), duration: *duration, events: Vec::new()}], LoopMode::Once)
+            },
+            (None, _) => return None,
+        };
+        animation.restart(now);
+        Some(animation)
+    }
 }
 
-/// Marks an entity as the keyboard controlled player
+/// Walks an entity to a destination around the obstacles of the world, by steering its
+/// `Locomotion`. Planned and followed by the `RouteFollowing` system, which removes it once the
+/// destination is reached.
 #[derive(Component, Debug, Clone)]
 #[storage(VecStorage)]
-pub struct Player {
-    /// The speed of the player when they are moving
-    pub movement_speed: i32,
+pub struct Route {
+    /// Where the entity is going, in world coordinates
+    pub destination: Point,
+    /// The points left to walk through in order, ending with the destination or the closest
+    /// point to it that can be reached. `None` until the route is planned.
+    pub waypoints: Option<Vec<Point>>,
+}
+
+impl Route {
+    /// A route to the given destination, to be planned before the next move
+    pub fn new(destination: Point) -> Self {
+        Self {destination, waypoints: None}
+    }
 }
 
-/// Marks an entity as an enemy that will cause damage to the player
+/// Marks an entity as the keyboard controlled player. The player moves with its `Locomotion`.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[storage(NullStorage)]
+pub struct Player;
+
+/// Marks an entity as an enemy that will cause damage to the player. Enemies follow their
+/// `IdleBehaviour` until they see the player, then react to them as long as they keep seeing them
+/// or remember where they saw them last. Once they give up, they go back to their post. Enemies
+/// with a `Behaviour` do whatever their behaviour tree says instead.
 #[derive(Component, Debug, Clone)]
 #[storage(VecStorage)]
 pub struct Enemy {
-    /// The amount of time elapsed since the direction was changed
-    pub direction_timer: Instant,
-    /// The amount of time to wait between direction changes
+    /// What the enemy does until it sees the player
+    pub idle: IdleBehaviour,
+    /// What the enemy does once it sees the player, unless it has a `Behaviour`
+    pub reaction: Reaction,
+    /// How far the enemy can see the player from, in pixels. The view is blocked by `Solid`
+    /// entities and solid tiles.
+    pub sight_radius: u32,
+    /// How long the enemy keeps reacting to the player after losing sight of them
+    pub memory: Duration,
+    /// Where the enemy goes back to once it stops reacting to the player, unless it patrols
+    pub post: Point,
+    /// What the enemy is doing. Always `EnemyMode::Idle` for enemies with a `Behaviour`.
+    pub mode: EnemyMode,
+    /// Where and at which `GameTime` the player was last seen, if ever
+    pub last_seen: Option<(Point, Duration)>,
+    /// The index of the patrol waypoint the enemy is walking to, when it patrols
+    pub next_waypoint: usize,
+    /// The `GameTime` at which the direction was last changed while wandering
+    pub direction_changed: Duration,
+    /// The amount of time to wait between direction changes while wandering
     pub direction_change_delay: Duration,
 }
 
+/// What an `Enemy` does while it has not seen the player
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub enum IdleBehaviour {
+    /// Walks around by randomly changing direction, and turns around when it runs into something
+    #[default]
+    Wander,
+    /// Stands still at its post
+    Guard,
+    /// Walks to each of the given points of the world in turn, around obstacles, and starts over
+    /// from the first one after the last one
+    Patrol(Vec<(i32, i32)>),
+}
+
+/// What an `Enemy` does once it sees the player
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Reaction {
+    /// Carries on with its `IdleBehaviour`
+    #[default]
+    Ignore,
+    /// Walks to the player around obstacles, or to where it last saw them
+    Chase,
+    /// Runs away from the player, or from where it last saw them
+    Flee,
+}
+
+/// What an `Enemy` is currently doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnemyMode {
+    /// Following its `IdleBehaviour`
+    Idle,
+    /// Chasing the player
+    Chasing,
+    /// Fleeing from the player
+    Fleeing,
+    /// Going back to its post after giving up on the player
+    Returning,
+}
+
+/// Drives an `Enemy` with a behaviour tree instead of its `Reaction`. The tree is ticked by the
+/// `AI` system along with the `Blackboard` of the entity, which it requires.
+#[derive(Component, Debug, Clone)]
+#[storage(VecStorage)]
+pub struct Behaviour(pub Arc<BehaviourTree>);
+
+/// What an entity driven by a `Behaviour` remembers from one tick of its tree to the next
+#[derive(Component, Debug, Default, Clone)]
+#[storage(VecStorage)]
+pub struct Blackboard {
+    /// The values read and written by the tree, by name
+    pub values: HashMap<String, BlackboardValue>,
+    /// What the nodes of the tree were doing at the last tick
+    pub tree: TreeState,
+}
+
 /// Marks an entity as the goal. If the player reaches this, they win the game.
 #[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
 #[storage(NullStorage)]
-pub struct Goal;
\ No newline at end of file
+pub struct Goal;
+
+/// Makes an entity stop when it runs into a `Solid` entity
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[storage(NullStorage)]
+pub struct Collider;
+
+/// Marks an entity as a static obstacle that `Collider` entities cannot walk through
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[storage(NullStorage)]
+pub struct Solid;
+
+/// The layer an entity's `Sprite` is drawn in. Layers are drawn from the first to the last, and
+/// within a layer, sprites are drawn from the top of the world to the bottom based on the bottom
+/// edge of their `BoundingBox`, so that an entity standing in front of another one hides it.
+/// Entities without this component are drawn in `RenderLayer::Objects`.
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[storage(VecStorage)]
+pub enum RenderLayer {
+    /// Flat things lying on the ground, drawn under everything else
+    Ground,
+    /// Characters, trees and everything else standing in the world
+    #[default]
+    Objects,
+    /// Things above the characters, like the foliage of a tall tree
+    Overhead,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steer_without_time_keeps_the_velocity() {
+        let locomotion = Locomotion {heading: Vector::new(1.0, 0.0), ..Locomotion::instant(100.0)};
+        let velocity = Vector::new(0.0, 50.0);
+        assert_eq!(locomotion.steer(velocity, 0.0), velocity);
+        let stopping = Locomotion::instant(100.0);
+        assert_eq!(stopping.steer(velocity, 0.0), velocity);
+    }
+
+    #[test]
+    fn steer_instantly_reaches_the_heading() {
+        let locomotion = Locomotion {heading: Vector::new(0.0, -1.0), ..Locomotion::instant(100.0)};
+        assert_eq!(locomotion.steer(Vector::new(30.0, 0.0), 1.0 / 60.0), Vector::new(0.0, -100.0));
+        assert_eq!(Locomotion::instant(100.0).steer(Vector::new(30.0, 0.0), 1.0 / 60.0), Vector::ZERO);
+    }
+}
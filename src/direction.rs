@@ -1,11 +1,18 @@
 use sdl2::rect::Point;
+use serde::{Serialize, Deserialize};
+
+use crate::vector::Vector;
 
 /// Represents a direction of motion
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
+    /// Towards the top of the screen
     Up,
+    /// Towards the bottom of the screen
     Down,
+    /// Towards the left of the screen
     Left,
+    /// Towards the right of the screen
     Right,
 }
 
@@ -20,4 +27,33 @@ impl Direction {
             Right => Point::new(1, 0),
         }
     }
-}
\ No newline at end of file
+
+    /// Returns a vector of length one pointing in this direction
+    pub fn into_vector(self) -> Vector {
+        Vector::from_point(self.into_point())
+    }
+
+    /// The direction closest to the given vector, i.e. along the axis of its longest component.
+    /// Vectors exactly between two directions are horizontal. The zero vector has no direction.
+    pub fn of(vector: Vector) -> Option<Self> {
+        use Direction::*;
+        if vector == Vector::ZERO {
+            None
+        } else if vector.x.abs() >= vector.y.abs() {
+            Some(if vector.x < 0.0 { Left } else { Right })
+        } else {
+            Some(if vector.y < 0.0 { Up } else { Down })
+        }
+    }
+
+    /// Returns the direction pointing the other way
+    pub fn opposite(self) -> Self {
+        use Direction::*;
+        match self {
+            Up => Down,
+            Down => Up,
+            Left => Right,
+            Right => Left,
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Setting up and updating the game world. Nothing in here depends on a window or on textures
+//! being loaded, so the same world can be driven by the SDL game loop or run headless.
+
+use std::cmp::Reverse;
+use std::time::Duration;
+
+use rand::{Rng, thread_rng};
+use sdl2::rect::Rect;
+use specs::{World, WorldExt, Dispatcher, DispatcherBuilder, System, Join};
+
+use crate::systems;
+use crate::assets::AssetManager;
+use crate::level::Level;
+use crate::headless::InputScript;
+use crate::pathfinding::{NavGrid, Pathfinder, PathSearch};
+use crate::components::{BoundingBox, RenderLayer, Solid};
+use crate::resources::{TimeDelta, GameTime, TimeScale, KeyboardEvent, GameStatus, InterpolationAlpha, GameRng};
+
+/// The amount of simulated time advanced by every update of the world. 1/60th of a second is 60
+/// updates per second, regardless of how fast the machine is able to render frames.
+pub const FIXED_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// A function populating a world of the given bounds with entities
+type Spawner = Box<dyn Fn(&mut World, Rect)>;
+
+/// Builds a [`Game`]: the systems to run every update and the entities to spawn in the world
+///
+/// ```no_run
+/// use learning_game_in_rust::game::GameBuilder;
+/// use learning_game_in_rust::level::Level;
+/// use learning_game_in_rust::resources::GameStatus;
+///
+/// let level = Level::load("assets/levels/pink_forest.ron").unwrap();
+/// let mut game = GameBuilder::new(level.world_bounds())
+///     .with_level(level)
+///     .build();
+/// while game.step(None) == GameStatus::Running {}
+/// ```
+pub struct GameBuilder {
+    world_bounds: Rect,
+    seed: Option<u64>,
+    path_search: PathSearch,
+    dispatcher_builder: DispatcherBuilder<'static, 'static>,
+    spawners: Vec<Spawner>,
+}
+
+impl GameBuilder {
+    /// A builder for a world of the given bounds, running every system of this game in dependency
+    /// order. No entity is spawned unless requested.
+    pub fn new(world_bounds: Rect) -> Self {
+        Self::empty(world_bounds)
+            .with_system(systems::Keyboard, "Keyboard", &[])
+            .with_system(systems::AI, "AI", &[])
+            .with_system(systems::RouteFollowing, "RouteFollowing", &["Keyboard", "AI"])
+            .with_system(systems::Movement {world_bounds}, "Movement", &["Keyboard", "AI", "RouteFollowing"])
+            .with_system(systems::SpatialIndexer, "SpatialIndexer", &["Movement"])
+            .with_system(systems::WinLoseChecker, "WinLoseChecker", &["SpatialIndexer"])
+            .with_system(systems::Animator, "Animator", &["Keyboard", "AI", "WinLoseChecker"])
+            .with_system(systems::CameraFollow {world_bounds}, "CameraFollow", &["Movement"])
+    }
+
+    /// A builder for a world of the given bounds without any system or entity
+    pub fn empty(world_bounds: Rect) -> Self {
+        Self {
+            world_bounds,
+            seed: None,
+            path_search: PathSearch::default(),
+            dispatcher_builder: DispatcherBuilder::new(),
+            spawners: Vec::new(),
+        }
+    }
+
+    /// Seeds the random number generator of the world. Without a seed, a random one is picked and
+    /// can be retrieved with [`Game::seed`] to reproduce the game later.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Changes how the `Pathfinder` of the world searches for paths
+    pub fn with_path_search(mut self, path_search: PathSearch) -> Self {
+        self.path_search = path_search;
+        self
+    }
+
+    /// Registers a system to run on every update, after the systems named in `dependencies`
+    pub fn with_system<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
+        where S: for<'c> System<'c> + Send + 'static
+    {
+        self.dispatcher_builder.add(system, name, dependencies);
+        self
+    }
+
+    /// Registers a function that populates the world once all systems have been set up. Spawners
+    /// are called in the order they were registered with the world and its bounds, and called
+    /// again whenever the game is restarted.
+    pub fn with_spawner<F>(mut self, spawner: F) -> Self
+        where F: Fn(&mut World, Rect) + 'static
+    {
+        self.spawners.push(Box::new(spawner));
+        self
+    }
+
+    /// Spawns the goal, the player and the enemies of the given level. The seed of the level is
+    /// used unless a seed was already given to the builder.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.seed = self.seed.or(level.seed);
+        self.with_spawner(move |world, _| level.spawn(world))
+    }
+
+    /// Sets up the world with the resources required by the registered systems and populates it
+    pub fn build(self) -> Game {
+        let GameBuilder {world_bounds, seed, path_search, dispatcher_builder, spawners} = self;
+        let mut dispatcher = dispatcher_builder.build();
+        let mut world = World::new();
+        dispatcher.setup(&mut world);
+        // Only read by the renderer, which is set up after the entities are spawned
+        world.register::<RenderLayer>();
+        // Read to build the navigation grid of the `Pathfinder`, whatever the systems
+        world.register::<Solid>();
+        world.register::<BoundingBox>();
+
+        world.insert(TimeScale::default());
+        world.insert(AssetManager::default());
+
+        let mut game = Game {world, dispatcher, tick: 0, world_bounds, seed, path_search, spawners};
+        game.populate();
+        game
+    }
+}
+
+/// A world and the systems updating it, advanced one fixed timestep at a time
+pub struct Game {
+    world: World,
+    dispatcher: Dispatcher<'static, 'static>,
+    tick: u64,
+    world_bounds: Rect,
+    /// The seed requested when building the game, if any
+    seed: Option<u64>,
+    path_search: PathSearch,
+    spawners: Vec<Spawner>,
+}
+
+impl Game {
+    /// The world being simulated, e.g. to inspect the components of its entities
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Mutable access to the world being simulated, e.g. to insert resources or spawn entities
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// The seed of the random number generator of the world
+    pub fn seed(&self) -> u64 {
+        self.world.read_resource::<GameRng>().seed()
+    }
+
+    /// The number of updates simulated so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Starts the game over: every entity is deleted, the state of the game is reset and the
+    /// spawners populate the world again. The random number generator is seeded with the seed
+    /// the game was built with, or with a new random seed if none was given. The `TimeScale` and
+    /// the textures registered in the `AssetManager` are kept.
+    pub fn restart(&mut self) {
+        // Deleted entities are handed out again last deleted first, so deleting them from the
+        // highest id down gives the new entities the same ids, and therefore the same join order,
+        // as in a newly built game. Otherwise a restarted game would play out differently.
+        let mut entities: Vec<_> = self.world.entities().join().collect();
+        entities.sort_unstable_by_key(|entity| Reverse(entity.id()));
+        self.world.delete_entities(&entities).expect("entities are alive");
+        self.world.maintain();
+        self.tick = 0;
+        self.populate();
+    }
+
+    /// Resets the state of the game, runs every spawner and lays out the obstacles they spawned
+    /// for the `Pathfinder`
+    fn populate(&mut self) {
+        self.world.insert(TimeDelta::default());
+        self.world.insert(GameTime::default());
+        self.world.insert(InterpolationAlpha::default());
+        self.world.insert(GameStatus::Running);
+        self.world.insert(GameRng::from_seed(self.seed.unwrap_or_else(|| thread_rng().gen())));
+        for spawner in &self.spawners {
+            spawner(&mut self.world, self.world_bounds);
+        }
+        let nav_grid = NavGrid::from_world(&self.world, self.world_bounds);
+        self.world.insert(Pathfinder::new(nav_grid, self.path_search));
+    }
+
+    /// The status of the game as of the last update
+    pub fn status(&self) -> GameStatus {
+        *self.world.read_resource::<GameStatus>()
+    }
+
+    /// How fast game time passes, see `TimeScale`
+    pub fn time_scale(&self) -> f64 {
+        self.world.read_resource::<TimeScale>().0
+    }
+
+    /// Changes how fast game time passes from the next update on, see `TimeScale`. Time scales
+    /// rejected by `TimeScale::is_valid`, e.g. negative, infinite or NaN, are ignored, and the
+    /// returned value tells whether the time scale was changed.
+    pub fn set_time_scale(&mut self, time_scale: f64) -> bool {
+        if !TimeScale::is_valid(time_scale) {
+            return false;
+        }
+        *self.world.write_resource() = TimeScale(time_scale);
+        true
+    }
+
+    /// UPDATE GAME
+    ///
+    /// Advances the world by a single `FIXED_TIMESTEP`, scaled by the `TimeScale`, with the given
+    /// input and returns the resulting status of the game
+    pub fn step(&mut self, keyboard_event: Option<KeyboardEvent>) -> GameStatus {
+        let time_delta = FIXED_TIMESTEP.mul_f64(self.time_scale());
+        self.world.insert(keyboard_event);
+        *self.world.write_resource() = TimeDelta(time_delta);
+        self.world.write_resource::<GameTime>().0 += time_delta;
+        self.dispatcher.dispatch(&self.world);
+        self.world.maintain();
+        self.tick += 1;
+        self.status()
+    }
+
+    /// Steps the world up to `ticks` times, feeding it the events of `script`. Stops as soon as
+    /// the game is won or lost and returns the final status of the game.
+    pub fn run(&mut self, ticks: u64, script: &InputScript) -> GameStatus {
+        let mut status = self.status();
+        for _ in 0..ticks {
+            if status != GameStatus::Running {
+                break;
+            }
+            status = self.step(script.event_at(self.tick));
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{Builder, Entity};
+
+    use super::*;
+    use crate::components::{Animation, Frame, LoopMode, Sprite};
+
+    /// A game running only the `Animator`, with a single entity playing frames of 50ms each
+    fn animated_game() -> (Game, Entity) {
+        let game = GameBuilder::empty(Rect::new(-100, -100, 200, 200))
+            .with_system(systems::Animator, "Animator", &[])
+            .with_spawner(|world, _| {
+                let texture = world.write_resource::<AssetManager>().texture("test.png");
+                let frames = (0..4).map(|frame| Frame {
+                    sprite: Sprite {texture, region: Rect::new(frame * 10, 0, 10, 10)},
+                    duration: Duration::from_millis(50),
+                    events: Vec::new(),
+                }).collect();
+                world.create_entity()
+                    .with(Animation::new(frames, LoopMode::Loop))
+                    .with(Sprite {texture, region: Rect::new(0, 0, 10, 10)})
+                    .build();
+            })
+            .with_seed(0)
+            .build();
+        let entity = game.world().entities().join().next().unwrap();
+        (game, entity)
+    }
+
+    /// Steps the game the given number of times and returns the frame the animation is at
+    fn frame_after(game: &mut Game, entity: Entity, ticks: u64) -> usize {
+        game.run(ticks, &InputScript::default());
+        game.world().read_storage::<Animation>().get(entity).unwrap().current_frame
+    }
+
+    fn game_time(game: &Game) -> Duration {
+        game.world().read_resource::<GameTime>().0
+    }
+
+    #[test]
+    fn timers_fire_after_enough_game_time() {
+        let (mut game, entity) = animated_game();
+        // Three steps of 1/60th of a second fall just short of 50ms
+        assert_eq!(frame_after(&mut game, entity, 3), 0);
+        assert_eq!(frame_after(&mut game, entity, 1), 1);
+        assert_eq!(game_time(&game), FIXED_TIMESTEP * 4);
+    }
+
+    #[test]
+    fn timers_do_not_fire_while_paused() {
+        let (mut game, entity) = animated_game();
+        assert_eq!(frame_after(&mut game, entity, 2), 0);
+        game.set_time_scale(0.0);
+        assert_eq!(frame_after(&mut game, entity, 600), 0);
+        assert_eq!(game_time(&game), FIXED_TIMESTEP * 2);
+        // Resumes where it was paused
+        game.set_time_scale(1.0);
+        assert_eq!(frame_after(&mut game, entity, 1), 0);
+        assert_eq!(frame_after(&mut game, entity, 1), 1);
+    }
+
+    #[test]
+    fn timers_follow_the_time_scale() {
+        let (mut game, entity) = animated_game();
+        game.set_time_scale(2.0);
+        assert_eq!(frame_after(&mut game, entity, 1), 0);
+        assert_eq!(frame_after(&mut game, entity, 1), 1);
+
+        game.set_time_scale(0.5);
+        // Six steps of half of 1/60th of a second fall just short of 50ms
+        assert_eq!(frame_after(&mut game, entity, 6), 1);
+        assert_eq!(frame_after(&mut game, entity, 1), 2);
+    }
+
+    #[test]
+    fn invalid_time_scales_are_ignored() {
+        let (mut game, entity) = animated_game();
+        assert!(game.set_time_scale(2.0));
+        for time_scale in [-1.0, f64::NAN, f64::INFINITY, f64::MAX] {
+            assert!(!game.set_time_scale(time_scale), "{} was accepted", time_scale);
+            assert_eq!(game.time_scale(), 2.0);
+        }
+        assert_eq!(frame_after(&mut game, entity, 2), 1);
+    }
+}
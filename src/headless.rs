@@ -0,0 +1,128 @@
+//! Support for running the game simulation without a window, a canvas or any textures. Input is
+//! scripted ahead of time instead of being read from an `EventPump`, which makes it possible to
+//! run the game in tests and on CI with [`Game::run`](crate::game::Game::run).
+
+use std::str::FromStr;
+
+use serde::{Serialize, Deserialize};
+
+use crate::direction::Direction;
+use crate::resources::KeyboardEvent;
+
+/// Keyboard events to deliver to the game at given ticks
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<(u64, KeyboardEvent)>", into = "Vec<(u64, KeyboardEvent)>")]
+pub struct InputScript {
+    /// The events of the script, as `(tick, event)` pairs sorted by tick
+    events: Vec<(u64, KeyboardEvent)>,
+}
+
+impl InputScript {
+    /// Creates a script from `(tick, event)` pairs, in any order
+    pub fn new(mut events: Vec<(u64, KeyboardEvent)>) -> Self {
+        events.sort_by_key(|&(tick, _)| tick);
+        Self {events}
+    }
+
+    /// Adds an event to deliver at the given tick, after any other event of the same tick
+    pub fn push(&mut self, tick: u64, keyboard_event: KeyboardEvent) {
+        let index = self.events.partition_point(|&(event_tick, _)| event_tick <= tick);
+        self.events.insert(index, (tick, keyboard_event));
+    }
+
+    /// The events of the script, as `(tick, event)` pairs sorted by tick
+    pub fn events(&self) -> &[(u64, KeyboardEvent)] {
+        &self.events
+    }
+
+    /// The event to deliver at the given tick, if any. When several events share the same tick,
+    /// the last one wins, just like when several SDL events arrive during the same frame.
+    pub fn event_at(&self, tick: u64) -> Option<KeyboardEvent> {
+        self.events.iter()
+            .rev()
+            .find(|&&(event_tick, _)| event_tick == tick)
+            .map(|&(_, event)| event)
+    }
+}
+
+impl From<Vec<(u64, KeyboardEvent)>> for InputScript {
+    fn from(events: Vec<(u64, KeyboardEvent)>) -> Self {
+        Self::new(events)
+    }
+}
+
+impl From<InputScript> for Vec<(u64, KeyboardEvent)> {
+    fn from(script: InputScript) -> Self {
+        script.events
+    }
+}
+
+/// Parses a comma separated list of `tick:event` entries where event is one of `up`, `down`,
+/// `left`, `right`, `stop`, `move X Y` to move with a heading, e.g. diagonally, or `to X Y` to
+/// walk to a point of the world. For example: `0:right,30:move 1 -1,90:stop,120:to -200 150`.
+impl FromStr for InputScript {
+    type Err = String;
+
+    fn from_str(script: &str) -> Result<Self, Self::Err> {
+        let mut events = Vec::new();
+        for entry in script.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (tick, event) = entry.split_once(':')
+                .ok_or_else(|| format!("Invalid script entry `{}`: expected `tick:event`", entry))?;
+            let tick = tick.trim().parse()
+                .map_err(|_| format!("Invalid tick `{}` in script entry `{}`", tick, entry))?;
+            let event = match event.trim() {
+                "up" => KeyboardEvent::MoveInDirection(Direction::Up),
+                "down" => KeyboardEvent::MoveInDirection(Direction::Down),
+                "left" => KeyboardEvent::MoveInDirection(Direction::Left),
+                "right" => KeyboardEvent::MoveInDirection(Direction::Right),
+                "stop" => KeyboardEvent::Stop,
+                other => if let Some(heading) = other.strip_prefix("move ") {
+                    let (x, y) = parse_pair(heading)
+                        .filter(|(x, y): &(f32, f32)| x.is_finite() && y.is_finite())
+                        .ok_or_else(|| format!("Invalid heading `{}` in script entry `{}`: expected `move X Y` with finite numbers", heading, entry))?;
+                    KeyboardEvent::Move {x, y}
+                } else if let Some(destination) = other.strip_prefix("to ") {
+                    let (x, y) = parse_pair(destination)
+                        .ok_or_else(|| format!("Invalid destination `{}` in script entry `{}`: expected `to X Y`", destination, entry))?;
+                    KeyboardEvent::MoveTo {x, y}
+                } else {
+                    return Err(format!("Unknown event `{}` in script entry `{}`", other, entry));
+                },
+            };
+            events.push((tick, event));
+        }
+        Ok(Self::new(events))
+    }
+}
+
+/// Parses the `X Y` coordinates of a `move X Y` or `to X Y` script event
+fn parse_pair<T: FromStr>(coordinates: &str) -> Option<(T, T)> {
+    let mut coordinates = coordinates.split_whitespace().map(str::parse);
+    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Some((x, y)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_parse_every_event() {
+        let script: InputScript = "30:move 1 -0.5, 0:right, 90:stop, 120:to -200 150".parse().unwrap();
+        assert_eq!(script.events(), &[
+            (0, KeyboardEvent::MoveInDirection(Direction::Right)),
+            (30, KeyboardEvent::Move {x: 1.0, y: -0.5}),
+            (90, KeyboardEvent::Stop),
+            (120, KeyboardEvent::MoveTo {x: -200, y: 150}),
+        ]);
+    }
+
+    #[test]
+    fn scripts_reject_non_finite_headings() {
+        for script in ["0:move NaN 0", "0:move inf 0", "0:move 1 -inf"] {
+            assert!(script.parse::<InputScript>().is_err(), "{} was accepted", script);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Text drawn in screen space, above the world, e.g. the elapsed time and the score. Unlike the
+//! sprites drawn by the renderer, text is positioned relative to the edges of the screen rather
+//! than through the `Camera`, so it stays in place while the camera moves.
+//!
+//! Text is drawn with a small bitmap font built into the game, so that no font file needs to be
+//! shipped or loaded. Each glyph is 5 by 7 pixels and is scaled up by whole pixels when drawn.
+//! Letters are drawn in upper case and characters missing from the font are drawn as `?`.
+
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::WindowCanvas,
+};
+
+/// The width of a glyph of the font, in font pixels
+pub const GLYPH_WIDTH: u32 = 5;
+/// The height of a glyph of the font, in font pixels
+pub const GLYPH_HEIGHT: u32 = 7;
+/// The space left between two glyphs of the same line, in font pixels
+const GLYPH_SPACING: u32 = 1;
+/// The space left below a line of text, in font pixels of that line
+const LINE_SPACING: u32 = 3;
+
+/// How a piece of text is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// The color of the text
+    pub color: Color,
+    /// The size of a font pixel on the screen, in screen pixels. A scale of 1 draws glyphs 5
+    /// pixels wide and 7 pixels tall.
+    pub scale: u32,
+    /// The color of a shadow drawn one font pixel below and to the right of the text, to keep the
+    /// text readable over any background
+    pub shadow: Option<Color>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::RGB(255, 255, 255),
+            scale: 3,
+            shadow: Some(Color::RGB(0, 0, 0)),
+        }
+    }
+}
+
+/// Where text is placed on the screen. Lines with the same anchor are laid out one below the
+/// other in the order they were added, and aligned with the edge of the screen they are anchored
+/// to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// The top left corner of the screen
+    TopLeft,
+    /// The middle of the top edge of the screen
+    Top,
+    /// The top right corner of the screen
+    TopRight,
+    /// The middle of the screen
+    Center,
+    /// The bottom left corner of the screen
+    BottomLeft,
+    /// The middle of the bottom edge of the screen
+    Bottom,
+    /// The bottom right corner of the screen
+    BottomRight,
+}
+
+/// Lines of text laid out on the screen, rebuilt every frame from the state of the game
+///
+/// ```no_run
+/// # fn draw(canvas: &mut sdl2::render::WindowCanvas) -> Result<(), String> {
+/// use learning_game_in_rust::hud::{Hud, Anchor, TextStyle};
+///
+/// let mut hud = Hud::new(16);
+/// hud.text(Anchor::TopLeft, "Time 0:42", TextStyle::default());
+/// hud.text(Anchor::TopRight, "Score 1200", TextStyle::default());
+/// hud.render(canvas)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Hud {
+    /// The distance between the text and the edges of the screen, in screen pixels
+    margin: u32,
+    lines: Vec<(Anchor, String, TextStyle)>,
+}
+
+impl Hud {
+    /// A HUD without any text, keeping the given distance from the edges of the screen
+    pub fn new(margin: u32) -> Self {
+        Self {margin, lines: Vec::new()}
+    }
+
+    /// Adds a line of text below the previous lines with the same anchor
+    pub fn text(&mut self, anchor: Anchor, text: impl Into<String>, style: TextStyle) -> &mut Self {
+        self.lines.push((anchor, text.into(), style));
+        self
+    }
+
+    /// The position of every line on a screen of the given size, along with its text and style
+    pub fn layout(&self, (screen_width, screen_height): (u32, u32)) -> Vec<(Point, &str, TextStyle)> {
+        let (screen_width, screen_height) = (screen_width as i32, screen_height as i32);
+        let margin = self.margin as i32;
+        let mut positions = Vec::with_capacity(self.lines.len());
+        for anchor in [Anchor::TopLeft, Anchor::Top, Anchor::TopRight, Anchor::Center, Anchor::BottomLeft, Anchor::Bottom, Anchor::BottomRight] {
+            let lines: Vec<_> = self.lines.iter().filter(|(line_anchor, ..)| *line_anchor == anchor).collect();
+            let line_heights: Vec<i32> = lines.iter()
+                .map(|(_, _, style)| ((GLYPH_HEIGHT + LINE_SPACING) * style.scale) as i32)
+                .collect();
+            // The spacing after the last line is not part of the block
+            let block_height = line_heights.iter().sum::<i32>()
+                - lines.last().map_or(0, |(_, _, style)| (LINE_SPACING * style.scale) as i32);
+            let mut y = match anchor {
+                Anchor::TopLeft | Anchor::Top | Anchor::TopRight => margin,
+                Anchor::Center => (screen_height - block_height) / 2,
+                Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => screen_height - margin - block_height,
+            };
+            for ((_, text, style), line_height) in lines.into_iter().zip(line_heights) {
+                let (width, _) = text_size(text, style.scale);
+                let x = match anchor {
+                    Anchor::TopLeft | Anchor::BottomLeft => margin,
+                    Anchor::Top | Anchor::Center | Anchor::Bottom => (screen_width - width as i32) / 2,
+                    Anchor::TopRight | Anchor::BottomRight => screen_width - margin - width as i32,
+                };
+                positions.push((Point::new(x, y), text.as_str(), *style));
+                y += line_height;
+            }
+        }
+        positions
+    }
+
+    /// Draws every line of text on the canvas
+    pub fn render(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let screen_size = canvas.output_size()?;
+        for (position, text, style) in self.layout(screen_size) {
+            draw_text(canvas, text, position, style)?;
+        }
+        Ok(())
+    }
+}
+
+/// The width and height of the given single line of text on the screen, in screen pixels
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let glyphs = text.chars().count() as u32;
+    let width = (glyphs * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING);
+    (width * scale, GLYPH_HEIGHT * scale)
+}
+
+/// Draws a single line of text with its top left corner at the given position on the screen
+pub fn draw_text(canvas: &mut WindowCanvas, text: &str, position: Point, style: TextStyle) -> Result<(), String> {
+    let TextStyle {color, scale, shadow} = style;
+    if let Some(shadow) = shadow {
+        let offset = scale as i32;
+        draw_glyphs(canvas, text, position.offset(offset, offset), scale, shadow)?;
+    }
+    draw_glyphs(canvas, text, position, scale, color)
+}
+
+fn draw_glyphs(canvas: &mut WindowCanvas, text: &str, position: Point, scale: u32, color: Color) -> Result<(), String> {
+    let mut pixels = Vec::new();
+    for (index, character) in text.chars().enumerate() {
+        let left = position.x() + (index as u32 * (GLYPH_WIDTH + GLYPH_SPACING) * scale) as i32;
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    pixels.push(Rect::new(
+                        left + (column * scale) as i32,
+                        position.y() + (row as u32 * scale) as i32,
+                        scale,
+                        scale,
+                    ));
+                }
+            }
+        }
+    }
+    canvas.set_draw_color(color);
+    canvas.fill_rects(&pixels)
+}
+
+/// The rows of the glyph of the given character, from top to bottom. The leftmost pixel of a row
+/// is its highest of the 5 lowest bits.
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT as usize] {
+    match character.to_ascii_uppercase() {
+        ' ' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000],
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+        '?' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '\'' => [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '*' => [0b00000, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b00000],
+        _ => glyph('?'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default style at the given scale. At a scale of 1, positions are in font pixels.
+    fn style(scale: u32) -> TextStyle {
+        TextStyle {scale, ..TextStyle::default()}
+    }
+
+    #[test]
+    fn text_size_counts_characters() {
+        assert_eq!(text_size("", 2), (0, 14));
+        assert_eq!(text_size("A", 1), (5, 7));
+        // Glyphs are 5 pixels wide with a pixel in between
+        assert_eq!(text_size("Time 0:42", 3), ((9 * 6 - 1) * 3, 21));
+        // Characters missing from the font take the room of the `?` drawn instead
+        assert_eq!(text_size("é€", 1), text_size("??", 1));
+        assert_eq!(glyph('é'), glyph('?'));
+        assert_eq!(glyph('a'), glyph('A'));
+    }
+
+    #[test]
+    fn lines_with_the_same_anchor_are_stacked() {
+        let mut hud = Hud::new(10);
+        hud.text(Anchor::TopLeft, "AB", style(1))
+            .text(Anchor::TopLeft, "C", style(2))
+            .text(Anchor::TopLeft, "D", style(1));
+        let positions: Vec<_> = hud.layout((200, 100)).into_iter().map(|(position, text, _)| (position, text)).collect();
+        assert_eq!(positions, vec![
+            (Point::new(10, 10), "AB"),
+            // Below the 7 pixels of the first line and its spacing of 3
+            (Point::new(10, 20), "C"),
+            (Point::new(10, 40), "D"),
+        ]);
+    }
+
+    #[test]
+    fn lines_are_aligned_with_the_edges_they_are_anchored_to() {
+        let mut hud = Hud::new(10);
+        hud.text(Anchor::BottomRight, "ABC", style(1))
+            .text(Anchor::BottomRight, "D", style(2))
+            .text(Anchor::TopRight, "AB", style(1))
+            .text(Anchor::Bottom, "A", style(1))
+            .text(Anchor::Center, "AB", style(1));
+        let positions: Vec<_> = hud.layout((200, 100)).into_iter().map(|(position, text, _)| (position, text)).collect();
+        assert_eq!(positions, vec![
+            (Point::new(200 - 10 - 11, 10), "AB"),
+            (Point::new((200 - 11) / 2, (100 - 7) / 2), "AB"),
+            (Point::new((200 - 5) / 2, 100 - 10 - 7), "A"),
+            // The block of both lines ends at the margin, without the spacing of its last line
+            (Point::new(200 - 10 - 17, 100 - 10 - 24), "ABC"),
+            (Point::new(200 - 10 - 10, 100 - 10 - 14), "D"),
+        ]);
+    }
+}
@@ -0,0 +1,547 @@
+//! Keys and game controller buttons are mapped to abstract `Action`s through an `InputConfig`, so
+//! that players can pick the keys that suit their keyboard layout or play with a gamepad. The
+//! `InputState` keeps track of the movement keys, buttons and analog sticks being held and turns
+//! them into the `KeyboardEvent`s delivered to the world. It also remembers where the player
+//! clicked, so that the player can walk to a point of the world with the mouse. SDL turns touches
+//! into mouse events, so tapping and dragging on a touch screen work the same way.
+//!
+//! See `assets/input.ron` for an example of the format of the config file.
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use sdl2::{
+    controller::{Axis, Button},
+    event::{Event, WindowEvent},
+    keyboard::Keycode,
+    mouse::MouseButton,
+    rect::Point,
+};
+use serde::Deserialize;
+
+use crate::direction::Direction;
+use crate::vector::Vector;
+use crate::resources::KeyboardEvent;
+
+/// How far an analog stick must be pushed, as a fraction of its range, before it moves the player
+/// when the config does not say otherwise
+const DEFAULT_STICK_DEAD_ZONE: f32 = 0.25;
+
+/// Something the player can do by pressing a key or a button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Action {
+    /// Move the player up for as long as the key is held
+    MoveUp,
+    /// Move the player down for as long as the key is held
+    MoveDown,
+    /// Move the player left for as long as the key is held
+    MoveLeft,
+    /// Move the player right for as long as the key is held
+    MoveRight,
+    /// Start the game from the title screen, or start a new game once the game is over
+    Confirm,
+    /// Restart a paused game
+    Restart,
+    /// Pause and resume the game
+    Pause,
+    /// Advance a paused game by a single update. Repeats while the key is held.
+    Step,
+    /// Halve the time scale
+    SlowDown,
+    /// Double the time scale
+    SpeedUp,
+    /// Reset the time scale
+    ResetTimeScale,
+    /// Show or hide the paths found by the `Pathfinder`
+    TogglePaths,
+    /// Close the game
+    Quit,
+}
+
+impl Action {
+    /// The direction of a movement action
+    pub fn direction(self) -> Option<Direction> {
+        match self {
+            Action::MoveUp => Some(Direction::Up),
+            Action::MoveDown => Some(Direction::Down),
+            Action::MoveLeft => Some(Direction::Left),
+            Action::MoveRight => Some(Direction::Right),
+            _ => None,
+        }
+    }
+
+    /// Whether the action happens again for every key repeat sent while its key is held down
+    pub fn repeats(self) -> bool {
+        self == Action::Step
+    }
+}
+
+/// The keys and game controller buttons bound to each action, as written in an input config file
+#[derive(Debug, Clone, Deserialize)]
+pub struct InputConfig {
+    /// The names of the keys bound to each action, as named by SDL (e.g. `"W"`, `"Up"`,
+    /// `"Space"`). An action can have any number of keys, but a key can only be bound to a single
+    /// action.
+    pub bindings: HashMap<Action, Vec<String>>,
+    /// The names of the game controller buttons bound to each action, as named by SDL (e.g.
+    /// `"dpup"`, `"a"`, `"start"`). Like keys, a button can only be bound to a single action.
+    /// Defaults to moving with the D-pad, confirming with A, restarting with Back and pausing
+    /// with Start.
+    #[serde(default = "default_buttons")]
+    pub buttons: HashMap<Action, Vec<String>>,
+    /// How far the left stick of a game controller must be pushed, as a fraction of its range,
+    /// before it moves the player. Small movements of a stick at rest are ignored.
+    #[serde(default = "default_stick_dead_zone")]
+    pub stick_dead_zone: f32,
+    /// The action of each key, built from `bindings` when the config is loaded
+    #[serde(skip)]
+    actions: HashMap<Keycode, Action>,
+    /// The action of each button, built from `buttons` when the config is loaded
+    #[serde(skip)]
+    button_actions: HashMap<Button, Action>,
+}
+
+fn default_buttons() -> HashMap<Action, Vec<String>> {
+    [
+        (Action::MoveUp, "dpup"),
+        (Action::MoveDown, "dpdown"),
+        (Action::MoveLeft, "dpleft"),
+        (Action::MoveRight, "dpright"),
+        (Action::Confirm, "a"),
+        (Action::Restart, "back"),
+        (Action::Pause, "start"),
+    ].into_iter().map(|(action, button)| (action, vec![button.to_string()])).collect()
+}
+
+fn default_stick_dead_zone() -> f32 {
+    DEFAULT_STICK_DEAD_ZONE
+}
+
+/// The reasons an input config file can fail to load
+#[derive(Debug)]
+pub enum InputConfigError {
+    /// The config file could not be read
+    Io {
+        /// The path of the config file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The config file is not valid RON or does not match the config format
+    Parse {
+        /// The path of the config file
+        path: PathBuf,
+        /// The underlying error, including the position of the error in the file
+        source: ron::error::SpannedError,
+    },
+    /// The config file is well-formed but its bindings cannot be used
+    Invalid {
+        /// The path of the config file
+        path: PathBuf,
+        /// What is wrong with the config
+        message: String,
+    },
+}
+
+impl fmt::Display for InputConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputConfigError::Io {path, source} => write!(f, "Could not read input config {}: {}", path.display(), source),
+            InputConfigError::Parse {path, source} => write!(f, "Could not parse input config {}: {}", path.display(), source),
+            InputConfigError::Invalid {path, message} => write!(f, "Invalid input config {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for InputConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InputConfigError::Io {source, ..} => Some(source),
+            InputConfigError::Parse {source, ..} => Some(source),
+            InputConfigError::Invalid {..} => None,
+        }
+    }
+}
+
+impl InputConfig {
+    /// Reads and validates the config file at the given path
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputConfigError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|source| InputConfigError::Io {path: path.to_path_buf(), source})?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses and validates a config. The `path` is only used in error messages.
+    pub fn parse(source: &str, path: impl AsRef<Path>) -> Result<Self, InputConfigError> {
+        let path = path.as_ref();
+        let mut config: InputConfig = ron::from_str(source)
+            .map_err(|source| InputConfigError::Parse {path: path.to_path_buf(), source})?;
+        let invalid = |message| InputConfigError::Invalid {path: path.to_path_buf(), message};
+        config.actions = resolve_bindings(&config.bindings, "key", Keycode::from_name).map_err(invalid)?;
+        config.button_actions = resolve_bindings(&config.buttons, "button", Button::from_string).map_err(invalid)?;
+        if !(0.0..1.0).contains(&config.stick_dead_zone) {
+            return Err(invalid(format!("stick_dead_zone must be at least 0 and less than 1, not {}", config.stick_dead_zone)));
+        }
+        Ok(config)
+    }
+
+    /// The action bound to the given key, if any
+    pub fn action(&self, key: Keycode) -> Option<Action> {
+        self.actions.get(&key).copied()
+    }
+
+    /// The action bound to the given game controller button, if any
+    pub fn button_action(&self, button: Button) -> Option<Action> {
+        self.button_actions.get(&button).copied()
+    }
+}
+
+/// Resolves the names of the keys or buttons of every binding with `from_name`, checking that
+/// each of them is only bound once
+fn resolve_bindings<T, F>(bindings: &HashMap<Action, Vec<String>>, kind: &str, from_name: F) -> Result<HashMap<T, Action>, String>
+    where T: Eq + Hash,
+          F: Fn(&str) -> Option<T>,
+{
+    let mut actions = HashMap::new();
+    for (&action, names) in bindings {
+        for name in names {
+            let input = from_name(name)
+                .ok_or_else(|| format!("unknown {} `{}` bound to {:?}", kind, name, action))?;
+            if let Some(other) = actions.insert(input, action) {
+                if other != action {
+                    return Err(format!("{} `{}` is bound to both {:?} and {:?}", kind, name, other, action));
+                }
+            }
+        }
+    }
+    Ok(actions)
+}
+
+/// Something held down by the player to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    /// A key of the keyboard
+    Key(Keycode),
+    /// A button of a game controller
+    Button {
+        /// The instance id of the game controller
+        controller: u32,
+        /// The button being held
+        button: Button,
+    },
+    /// The left stick of a game controller, pushed past the dead zone
+    Stick {
+        /// The instance id of the game controller
+        controller: u32,
+    },
+}
+
+impl InputSource {
+    /// The instance id of the game controller of the source, if it is on a game controller
+    pub fn controller(self) -> Option<u32> {
+        match self {
+            InputSource::Key(_) => None,
+            InputSource::Button {controller, ..} | InputSource::Stick {controller} => Some(controller),
+        }
+    }
+}
+
+/// The movement keys, buttons and sticks being held down. Holding a horizontal and a vertical
+/// direction together moves the player diagonally. When several inputs of the same axis are held,
+/// the one pressed last wins, and the others take over again as it is released, so that changing
+/// direction never stops the player in between. A stick pushed after every other input moves the
+/// player with its own analog heading.
+#[derive(Debug, Default, Clone)]
+pub struct InputState {
+    /// The inputs being held, from the first pressed to the last. Sticks are held in the
+    /// direction they were pushed in when they left the dead zone.
+    held: Vec<(InputSource, Direction)>,
+    /// The position of the left stick of each game controller, from -1 to 1 on both axes
+    sticks: HashMap<u32, Vector>,
+    /// How far sticks must be pushed before they move the player, see `InputConfig`
+    dead_zone: f32,
+    /// The heading reported to the world by the last call to `take_event`, if any
+    reported: Option<Vector>,
+    /// The last point of the screen pointed at since the last call to `take_pointer`, and whether
+    /// the left mouse button was pressed rather than only dragged in the meantime
+    pointer: Option<(Point, bool)>,
+}
+
+impl InputState {
+    /// Updates the held inputs from the given event, and returns the action it triggers other
+    /// than moving, if any
+    pub fn handle_event(&mut self, event: &Event, config: &InputConfig) -> Option<Action> {
+        let (source, action, pressed) = match *event {
+            Event::Quit {..} => return Some(Action::Quit),
+            Event::Window {win_event: WindowEvent::FocusLost, ..} => {
+                self.release_all();
+                return None;
+            },
+            Event::KeyDown {keycode: Some(key), repeat, ..} => {
+                let action = config.action(key)?;
+                if repeat && !action.repeats() {
+                    return None;
+                }
+                (InputSource::Key(key), action, true)
+            },
+            Event::KeyUp {keycode: Some(key), ..} => (InputSource::Key(key), config.action(key)?, false),
+            Event::ControllerButtonDown {which, button, ..} => {
+                (InputSource::Button {controller: which, button}, config.button_action(button)?, true)
+            },
+            Event::ControllerButtonUp {which, button, ..} => {
+                (InputSource::Button {controller: which, button}, config.button_action(button)?, false)
+            },
+            Event::ControllerAxisMotion {which, axis, value, ..} => {
+                self.move_stick(which, axis, value, config.stick_dead_zone);
+                return None;
+            },
+            Event::ControllerDeviceRemoved {which, ..} => {
+                self.release_controller(which);
+                return None;
+            },
+            Event::MouseButtonDown {mouse_btn: MouseButton::Left, x, y, ..} => {
+                self.point_at(Point::new(x, y), true);
+                return None;
+            },
+            Event::MouseMotion {mousestate, x, y, ..} if mousestate.left() => {
+                self.point_at(Point::new(x, y), false);
+                return None;
+            },
+            _ => return None,
+        };
+        match (action.direction(), pressed) {
+            (Some(direction), true) => self.press(source, direction),
+            (Some(_), false) => self.release(source),
+            (None, true) => return Some(action),
+            (None, false) => {},
+        }
+        None
+    }
+
+    /// Records that an input bound to a movement in the given direction was pressed
+    pub fn press(&mut self, source: InputSource, direction: Direction) {
+        self.held.retain(|&(held, _)| held != source);
+        self.held.push((source, direction));
+    }
+
+    /// Records that the given input was released
+    pub fn release(&mut self, source: InputSource) {
+        self.held.retain(|&(held, _)| held != source);
+    }
+
+    /// Forgets every input being held, e.g. when the window loses focus and key releases can no
+    /// longer be seen
+    pub fn release_all(&mut self) {
+        self.held.clear();
+        self.sticks.clear();
+    }
+
+    /// Forgets every input of the given game controller, e.g. when it is unplugged
+    pub fn release_controller(&mut self, controller: u32) {
+        self.held.retain(|&(held, _)| held.controller() != Some(controller));
+        self.sticks.remove(&controller);
+    }
+
+    /// The last point of the screen the player clicked or dragged the mouse to since the last
+    /// call, if any, along with whether the left mouse button was pressed rather than only
+    /// dragged in the meantime
+    pub fn take_pointer(&mut self) -> Option<(Point, bool)> {
+        self.pointer.take()
+    }
+
+    fn point_at(&mut self, point: Point, pressed: bool) {
+        let pressed = pressed || self.pointer.is_some_and(|(_, pressed)| pressed);
+        self.pointer = Some((point, pressed));
+    }
+
+    /// Updates the position of the left stick of a game controller. The stick starts moving the
+    /// player once it is pushed further than `dead_zone` from its center.
+    fn move_stick(&mut self, controller: u32, axis: Axis, value: i16, dead_zone: f32) {
+        let stick = self.sticks.entry(controller).or_default();
+        let value = (value as f32 / i16::MAX as f32).max(-1.0);
+        match axis {
+            Axis::LeftX => stick.x = value,
+            Axis::LeftY => stick.y = value,
+            _ => return,
+        }
+        let stick = *stick;
+        self.dead_zone = dead_zone;
+
+        let source = InputSource::Stick {controller};
+        let held = self.held.iter().any(|&(held, _)| held == source);
+        // Pushing a stick further does not take priority over the inputs pressed since
+        match (held, Direction::of(stick)) {
+            (false, Some(direction)) if stick.length() > dead_zone => self.press(source, direction),
+            (true, _) if stick.length() <= dead_zone => self.release(source),
+            _ => {},
+        }
+    }
+
+    /// Makes the next call to `take_event` report the current heading even if it did not
+    /// change, e.g. for a new game whose player starts out standing still
+    pub fn report_again(&mut self) {
+        self.reported = None;
+    }
+
+    /// The heading the player should move with, see `Locomotion::heading`. Zero when the player
+    /// should stand still.
+    pub fn heading(&self) -> Vector {
+        match self.held.last() {
+            Some(&(InputSource::Stick {controller}, _)) => self.stick_heading(controller),
+            Some(_) => {
+                let last_held = |directions: [Direction; 2]| self.held.iter()
+                    .rev()
+                    .find(|&&(source, direction)| !matches!(source, InputSource::Stick {..}) && directions.contains(&direction))
+                    .map_or(Vector::ZERO, |&(_, direction)| direction.into_vector());
+                (last_held([Direction::Left, Direction::Right]) + last_held([Direction::Up, Direction::Down])).normalized()
+            },
+            None => Vector::ZERO,
+        }
+    }
+
+    /// The heading of the given stick, growing from zero at the edge of the dead zone to one when
+    /// the stick is pushed all the way
+    fn stick_heading(&self, controller: u32) -> Vector {
+        let stick = self.sticks.get(&controller).copied().unwrap_or_default();
+        let length = stick.length();
+        if length <= self.dead_zone {
+            return Vector::ZERO;
+        }
+        let strength = ((length - self.dead_zone) / (1.0 - self.dead_zone)).min(1.0);
+        stick.normalized() * strength
+    }
+
+    /// The event telling the world about a change of `heading` since the last call, if any
+    pub fn take_event(&mut self) -> Option<KeyboardEvent> {
+        let heading = self.heading();
+        if Some(heading) == self.reported {
+            return None;
+        }
+        self.reported = Some(heading);
+        Some(if heading == Vector::ZERO { KeyboardEvent::Stop } else { KeyboardEvent::Move {x: heading.x, y: heading.y} })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::keyboard::Mod;
+
+    use super::*;
+
+    const CONTROLLER: u32 = 3;
+
+    /// Moves with the arrow keys and the D-pad, and ignores sticks pushed less than a quarter of
+    /// their range
+    fn config() -> InputConfig {
+        let movements = [
+            (Action::MoveUp, Keycode::Up, Button::DPadUp),
+            (Action::MoveDown, Keycode::Down, Button::DPadDown),
+            (Action::MoveLeft, Keycode::Left, Button::DPadLeft),
+            (Action::MoveRight, Keycode::Right, Button::DPadRight),
+        ];
+        InputConfig {
+            bindings: HashMap::new(),
+            buttons: HashMap::new(),
+            stick_dead_zone: 0.25,
+            actions: movements.iter().map(|&(action, key, _)| (key, action)).collect(),
+            button_actions: movements.iter().map(|&(action, _, button)| (button, action)).collect(),
+        }
+    }
+
+    fn key(key: Keycode, pressed: bool) -> Event {
+        match pressed {
+            true => Event::KeyDown {timestamp: 0, window_id: 0, keycode: Some(key), scancode: None, keymod: Mod::NOMOD, repeat: false},
+            false => Event::KeyUp {timestamp: 0, window_id: 0, keycode: Some(key), scancode: None, keymod: Mod::NOMOD, repeat: false},
+        }
+    }
+
+    fn button(button: Button, pressed: bool) -> Event {
+        match pressed {
+            true => Event::ControllerButtonDown {timestamp: 0, which: CONTROLLER, button},
+            false => Event::ControllerButtonUp {timestamp: 0, which: CONTROLLER, button},
+        }
+    }
+
+    /// The left stick pushed to the given fraction of its range along the given axis
+    fn stick(axis: Axis, fraction: f32) -> Event {
+        Event::ControllerAxisMotion {timestamp: 0, which: CONTROLLER, axis, value: (fraction * i16::MAX as f32) as i16}
+    }
+
+    fn handle(input: &mut InputState, events: &[Event]) {
+        let config = config();
+        for event in events {
+            assert_eq!(input.handle_event(event, &config), None);
+        }
+    }
+
+    fn assert_heading(input: &InputState, x: f32, y: f32) {
+        let heading = input.heading();
+        assert!((heading.x - x).abs() < 1e-3 && (heading.y - y).abs() < 1e-3, "expected ({}, {}), got {:?}", x, y, heading);
+    }
+
+    #[test]
+    fn sticks_only_move_the_player_out_of_the_dead_zone() {
+        let mut input = InputState::default();
+        handle(&mut input, &[stick(Axis::LeftX, 0.2)]);
+        assert_heading(&input, 0.0, 0.0);
+        assert!(input.held.is_empty());
+
+        // Halfway between the edge of the dead zone and the edge of the range
+        handle(&mut input, &[stick(Axis::LeftX, 0.625)]);
+        assert_heading(&input, 0.5, 0.0);
+        handle(&mut input, &[stick(Axis::LeftY, -1.0)]);
+        let heading = input.heading();
+        assert!((heading.length() - 1.0).abs() < 1e-3 && heading.y < -heading.x, "{:?}", heading);
+
+        handle(&mut input, &[stick(Axis::LeftY, 0.0), stick(Axis::LeftX, 0.1)]);
+        assert_heading(&input, 0.0, 0.0);
+        assert!(input.held.is_empty());
+    }
+
+    #[test]
+    fn the_input_pressed_last_takes_over() {
+        let mut input = InputState::default();
+        handle(&mut input, &[key(Keycode::Left, true)]);
+        assert_heading(&input, -1.0, 0.0);
+
+        // The stick pushed last moves the player with its own heading
+        handle(&mut input, &[stick(Axis::LeftX, 1.0)]);
+        assert_heading(&input, 1.0, 0.0);
+
+        // Keys and buttons pressed since combine with each other, but not with the stick
+        handle(&mut input, &[button(Button::DPadUp, true)]);
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        assert_heading(&input, -diagonal, -diagonal);
+        handle(&mut input, &[key(Keycode::Down, true)]);
+        assert_heading(&input, -diagonal, diagonal);
+        handle(&mut input, &[key(Keycode::Down, false)]);
+        assert_heading(&input, -diagonal, -diagonal);
+
+        // The stick takes over again once the inputs pressed after it are released
+        handle(&mut input, &[button(Button::DPadUp, false)]);
+        assert_heading(&input, 1.0, 0.0);
+        handle(&mut input, &[stick(Axis::LeftX, 0.0)]);
+        assert_heading(&input, -1.0, 0.0);
+        handle(&mut input, &[key(Keycode::Left, false)]);
+        assert_heading(&input, 0.0, 0.0);
+    }
+
+    #[test]
+    fn removing_a_controller_forgets_its_inputs() {
+        let mut input = InputState::default();
+        handle(&mut input, &[key(Keycode::Right, true), button(Button::DPadDown, true), stick(Axis::LeftX, -1.0)]);
+        assert_heading(&input, -1.0, 0.0);
+
+        handle(&mut input, &[Event::ControllerDeviceRemoved {timestamp: 0, which: CONTROLLER}]);
+        assert!(input.sticks.is_empty());
+        assert_heading(&input, 1.0, 0.0);
+        // A stick reported again after the controller is plugged back in starts from its center
+        handle(&mut input, &[stick(Axis::LeftY, 0.1)]);
+        assert_heading(&input, 1.0, 0.0);
+    }
+}
@@ -0,0 +1,775 @@
+//! Levels are described in RON files so that they can be authored without recompiling the game.
+//! A level describes the bounds of the world, the sprite sheets used by its entities, the tile map
+//! drawn beneath them, the behaviour trees of its enemies and where the player, the goal, the
+//! enemies and the obstacles are spawned.
+//!
+//! See `assets/levels/pink_forest.ron` for an example of the format.
+
+use std::fmt;
+use std::collections::HashMap;
+use std::fs;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
+use sdl2::rect::{Point, Rect};
+use serde::Deserialize;
+use specs::{World, WorldExt, Builder};
+
+use crate::assets::{AssetManager, TextureHandle};
+use crate::direction::Direction;
+use crate::vector::Vector;
+use crate::resources::{GameRng, GameTime};
+use crate::tilemap::{TileMap, TileMapError};
+use crate::behaviour_tree::{BehaviourTree, BehaviourTreeError};
+use crate::sprite_sheet::{SpriteSheetDescriptor, SpriteSheetError};
+use crate::components::{
+    BoundingBox,
+    Position,
+    Velocity,
+    Locomotion,
+    Sprite,
+    DirectionalAnimations,
+    AnimationState,
+    AnimationStateMachine,
+    LoopMode,
+    Player,
+    Enemy,
+    IdleBehaviour,
+    Reaction,
+    EnemyMode,
+    Route,
+    Behaviour,
+    Blackboard,
+    Goal,
+    Collider,
+    Solid,
+    RenderLayer,
+};
+
+/// A level as written in a level file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Level {
+    /// The name of the level
+    pub name: String,
+    /// The seed of the random number generator, to play the exact same game every time
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// The width and height of the world. The world is centered on (0, 0).
+    pub world_size: (u32, u32),
+    /// The sprite sheets used by the entities of the level
+    pub sprite_sheets: Vec<SpriteSheetRef>,
+    /// The behaviour trees driving the enemies of the level
+    #[serde(default)]
+    pub behaviours: Vec<BehaviourRef>,
+    /// Where and how the player is spawned
+    pub player: PlayerSpawn,
+    /// Where and how the goal is spawned
+    pub goal: GoalSpawn,
+    /// Where and how each enemy is spawned
+    pub enemies: Vec<EnemySpawn>,
+    /// Static obstacles that the player and the enemies cannot walk through
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleSpawn>,
+    /// The path of a tile map exported by Tiled in JSON, relative to the working directory of the
+    /// game. The map is placed at the top-left corner of the world.
+    #[serde(default)]
+    pub map: Option<PathBuf>,
+    /// The tile map loaded from `map`
+    #[serde(skip)]
+    pub tile_map: Option<TileMap>,
+}
+
+/// A named reference to a sprite sheet image
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheetRef {
+    /// The name used by entities to refer to this sprite sheet
+    pub name: String,
+    /// The path of the image, relative to the working directory of the game
+    pub path: PathBuf,
+    /// The path of a descriptor naming the animations of the sprite sheet (see the
+    /// `sprite_sheet` module), relative to the working directory of the game
+    #[serde(default)]
+    pub animations: Option<PathBuf>,
+    /// The descriptor loaded from `animations`
+    #[serde(skip)]
+    pub descriptor: Option<SpriteSheetDescriptor>,
+}
+
+/// A named reference to a behaviour tree file
+#[derive(Debug, Clone, Deserialize)]
+pub struct BehaviourRef {
+    /// The name used by enemies to refer to this behaviour tree
+    pub name: String,
+    /// The path of the behaviour tree file (see the `behaviour_tree` module), relative to the
+    /// working directory of the game
+    pub path: PathBuf,
+    /// The tree loaded from `path`, shared by every enemy using it
+    #[serde(skip)]
+    pub tree: Option<Arc<BehaviourTree>>,
+}
+
+/// Where an entity is spawned, in world coordinates
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum SpawnPosition {
+    /// Always at the given position
+    Fixed(i32, i32),
+    /// At a random position within the given inclusive ranges
+    Random {
+        /// The minimum and maximum x coordinate
+        x: (i32, i32),
+        /// The minimum and maximum y coordinate
+        y: (i32, i32),
+    },
+}
+
+/// The direction an entity is facing when spawned
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum SpawnDirection {
+    /// Always the given direction
+    Fixed(Direction),
+    /// A random direction
+    Random,
+}
+
+/// A single region of a sprite sheet
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteRef {
+    /// The name of the sprite sheet
+    pub sheet: String,
+    /// The `(x, y, width, height)` region of the sprite sheet to draw
+    pub region: (i32, i32, u32, u32),
+}
+
+/// The animations of a character, e.g. `Standard(sheet: "bardo", ...)` or
+/// `Named(sheet: "reaper", ...)`
+#[derive(Debug, Clone, Deserialize)]
+pub enum AnimationsRef {
+    /// Walking animations laid out in the standard way, and no other states
+    Standard(WalkingAnimationsRef),
+    /// Animations taken from the descriptor of a sprite sheet
+    Named(NamedAnimationsRef),
+}
+
+/// Walking animations following the standard sprite sheet layout of this project (see
+/// `DirectionalAnimations::standard_walking_animations`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct WalkingAnimationsRef {
+    /// The name of the sprite sheet
+    pub sheet: String,
+    /// The `(x, y, width, height)` region of the first frame of the sprite sheet
+    pub top_left_frame: (i32, i32, u32, u32),
+    /// The number of frames of each animation
+    pub frames_length: usize,
+    /// The duration of each frame, in milliseconds
+    pub step_delay_ms: u64,
+}
+
+/// Animations named in the descriptor of a sprite sheet
+#[derive(Debug, Clone, Deserialize)]
+pub struct NamedAnimationsRef {
+    /// The name of the sprite sheet, which must have a descriptor
+    pub sheet: String,
+    /// The name of the animation played when walking up
+    pub walking_up: String,
+    /// The name of the animation played when walking down
+    pub walking_down: String,
+    /// The name of the animation played when walking left
+    pub walking_left: String,
+    /// The name of the animation played when walking right
+    pub walking_right: String,
+    /// The animations of the other states of the character, e.g. `Idle` or `Death`
+    #[serde(default)]
+    pub states: HashMap<AnimationState, AnimationNames>,
+}
+
+/// The names of the animations of a single `AnimationState`, e.g. `Same("death")` or
+/// `Directional(up: "idle_up", ...)`
+#[derive(Debug, Clone, Deserialize)]
+pub enum AnimationNames {
+    /// The same animation whatever the direction the character faces
+    Same(String),
+    /// One animation for each direction the character can face
+    Directional {
+        /// The name of the animation played when facing up
+        up: String,
+        /// The name of the animation played when facing down
+        down: String,
+        /// The name of the animation played when facing left
+        left: String,
+        /// The name of the animation played when facing right
+        right: String,
+    },
+}
+
+impl AnimationNames {
+    fn names(&self) -> [&String; 4] {
+        match self {
+            AnimationNames::Same(name) => [name, name, name, name],
+            AnimationNames::Directional {up, down, left, right} => [up, down, left, right],
+        }
+    }
+}
+
+/// How the player is spawned
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlayerSpawn {
+    /// Where the player starts
+    pub position: SpawnPosition,
+    /// The width and height of the player's bounding box
+    pub size: (u32, u32),
+    /// The speed of the player when they are moving, in pixels per second
+    pub movement_speed: f32,
+    /// How fast the player speeds up, in pixels per second squared. Reaches full speed at once
+    /// when missing.
+    #[serde(default)]
+    pub acceleration: Option<f32>,
+    /// How fast the player slows down once they stop moving, in pixels per second squared. Stops
+    /// at once when missing.
+    #[serde(default)]
+    pub friction: Option<f32>,
+    /// The walking animations of the player
+    pub animations: AnimationsRef,
+}
+
+/// How the goal is spawned
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoalSpawn {
+    /// Where the goal is placed
+    pub position: SpawnPosition,
+    /// The width and height of the goal's bounding box
+    pub size: (u32, u32),
+    /// The sprite of the goal
+    pub sprite: SpriteRef,
+}
+
+/// How an enemy is spawned
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemySpawn {
+    /// Where the enemy starts
+    pub position: SpawnPosition,
+    /// The direction the enemy starts walking in
+    pub direction: SpawnDirection,
+    /// The width and height of the enemy's bounding box
+    pub size: (u32, u32),
+    /// The speed of the enemy, in pixels per second
+    pub speed: f32,
+    /// How fast the enemy speeds up and turns around, in pixels per second squared. Reaches full
+    /// speed at once when missing.
+    #[serde(default)]
+    pub acceleration: Option<f32>,
+    /// How fast the enemy slows down when it stops, in pixels per second squared. Stops at once
+    /// when missing.
+    #[serde(default)]
+    pub friction: Option<f32>,
+    /// The amount of time to wait between direction changes while wandering, in milliseconds
+    pub direction_change_delay_ms: u64,
+    /// What the enemy does until it sees the player. Wanders when missing.
+    #[serde(default)]
+    pub idle: IdleBehaviour,
+    /// What the enemy does once it sees the player. Ignores them when missing.
+    #[serde(default)]
+    pub on_sight: Reaction,
+    /// The name of a behaviour tree of the level to drive the enemy with instead of `on_sight`
+    #[serde(default)]
+    pub behaviour: Option<String>,
+    /// How far the enemy can see the player from, in pixels
+    #[serde(default)]
+    pub sight_radius: u32,
+    /// How long the enemy keeps reacting to the player after losing sight of them, in milliseconds
+    #[serde(default = "default_memory_ms")]
+    pub memory_ms: u64,
+    /// The walking animations of the enemy
+    pub animations: AnimationsRef,
+}
+
+/// How a static obstacle is spawned
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObstacleSpawn {
+    /// Where the obstacle is placed
+    pub position: SpawnPosition,
+    /// The width and height of the obstacle's bounding box
+    pub size: (u32, u32),
+    /// The sprite of the obstacle, if it is visible
+    #[serde(default)]
+    pub sprite: Option<SpriteRef>,
+    /// The layer the sprite of the obstacle is drawn in
+    #[serde(default)]
+    pub layer: RenderLayer,
+}
+
+/// The reasons a level file can fail to load
+#[derive(Debug)]
+pub enum LevelError {
+    /// The level file could not be read
+    Io {
+        /// The path of the level file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The level file is not valid RON or does not match the level format
+    Parse {
+        /// The path of the level file
+        path: PathBuf,
+        /// The underlying error, including the position of the error in the file
+        source: ron::error::SpannedError,
+    },
+    /// The level file is well-formed but describes a level that cannot be built
+    Invalid {
+        /// The path of the level file
+        path: PathBuf,
+        /// What is wrong with the level
+        message: String,
+    },
+    /// The descriptor of one of the sprite sheets of the level could not be loaded
+    SpriteSheet {
+        /// The path of the level file
+        path: PathBuf,
+        /// The underlying error, boxed because it is much larger than the other variants
+        source: Box<SpriteSheetError>,
+    },
+    /// One of the behaviour trees of the level could not be loaded
+    Behaviour {
+        /// The path of the level file
+        path: PathBuf,
+        /// The underlying error, boxed because it is much larger than the other variants
+        source: Box<BehaviourTreeError>,
+    },
+    /// The tile map of the level could not be loaded
+    Map {
+        /// The path of the level file
+        path: PathBuf,
+        /// The underlying error
+        source: TileMapError,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io {path, source} => write!(f, "Could not read level {}: {}", path.display(), source),
+            LevelError::Parse {path, source} => write!(f, "Could not parse level {}: {}", path.display(), source),
+            LevelError::Invalid {path, message} => write!(f, "Invalid level {}: {}", path.display(), message),
+            LevelError::SpriteSheet {path, source} => write!(f, "Could not load a sprite sheet of level {}: {}", path.display(), source),
+            LevelError::Behaviour {path, source} => write!(f, "Could not load a behaviour tree of level {}: {}", path.display(), source),
+            LevelError::Map {path, source} => write!(f, "Could not load the map of level {}: {}", path.display(), source),
+        }
+    }
+}
+
+impl Error for LevelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LevelError::Io {source, ..} => Some(source),
+            LevelError::Parse {source, ..} => Some(source),
+            LevelError::Invalid {..} => None,
+            LevelError::SpriteSheet {source, ..} => Some(source.as_ref()),
+            LevelError::Behaviour {source, ..} => Some(source.as_ref()),
+            LevelError::Map {source, ..} => Some(source),
+        }
+    }
+}
+
+impl Level {
+    /// Reads and validates the level file at the given path
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LevelError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|source| LevelError::Io {path: path.to_path_buf(), source})?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses a level, loads its sprite sheet descriptors and behaviour trees, validates it and
+    /// then loads its tile map. The `path` is only used in error messages.
+    pub fn parse(source: &str, path: impl AsRef<Path>) -> Result<Self, LevelError> {
+        let path = path.as_ref();
+        // Lets levels write `Standard(sheet: ...)` rather than `Standard((sheet: ...))`
+        let options = ron::Options::default().with_default_extension(ron::extensions::Extensions::UNWRAP_VARIANT_NEWTYPES);
+        let mut level: Level = options.from_str(source)
+            .map_err(|source| LevelError::Parse {path: path.to_path_buf(), source})?;
+        // Loaded before validating the level, which checks the names of the animations it uses
+        for sprite_sheet in &mut level.sprite_sheets {
+            if let Some(animations) = &sprite_sheet.animations {
+                let descriptor = SpriteSheetDescriptor::load(animations)
+                    .map_err(|source| LevelError::SpriteSheet {path: path.to_path_buf(), source: Box::new(source)})?;
+                sprite_sheet.descriptor = Some(descriptor);
+            }
+        }
+        for behaviour in &mut level.behaviours {
+            let tree = BehaviourTree::load(&behaviour.path)
+                .map_err(|source| LevelError::Behaviour {path: path.to_path_buf(), source: Box::new(source)})?;
+            behaviour.tree = Some(Arc::new(tree));
+        }
+        level.validate()
+            .map_err(|message| LevelError::Invalid {path: path.to_path_buf(), message})?;
+        if let Some(map) = &level.map {
+            let tile_map = TileMap::load_tiled_json(map, level.world_bounds().top_left())
+                .map_err(|source| LevelError::Map {path: path.to_path_buf(), source})?;
+            level.tile_map = Some(tile_map);
+        }
+        Ok(level)
+    }
+
+    /// The bounds of the world of this level, centered on (0, 0)
+    pub fn world_bounds(&self) -> Rect {
+        let (width, height) = self.world_size;
+        Rect::from_center((0, 0), width, height)
+    }
+
+    /// The sprite sheet with the given name
+    pub fn sprite_sheet(&self, name: &str) -> Option<&SpriteSheetRef> {
+        self.sprite_sheets.iter().find(|sprite_sheet| sprite_sheet.name == name)
+    }
+
+    /// The behaviour tree with the given name
+    pub fn behaviour(&self, name: &str) -> Option<&BehaviourRef> {
+        self.behaviours.iter().find(|behaviour| behaviour.name == name)
+    }
+
+    /// Spawns the goal, the player, the enemies and the obstacles of this level in the world and
+    /// inserts its `TileMap`. Random positions and directions are picked with the `GameRng` of the
+    /// world, and every image used by the level is registered in its `AssetManager`.
+    pub fn spawn(&self, world: &mut World) {
+        // Taken out of the world while entities are being created and put back afterwards
+        let mut rng = world.remove::<GameRng>().expect("the world must have a GameRng to spawn a level");
+        let mut assets = world.remove::<AssetManager>().unwrap_or_default();
+        let GameTime(now) = *world.read_resource::<GameTime>();
+
+        if let Some(tile_map) = &self.tile_map {
+            let mut tile_map = tile_map.clone();
+            tile_map.tileset.texture = Some(assets.texture(&tile_map.tileset.image));
+            world.insert(tile_map);
+        }
+
+        let GoalSpawn {position, size: (width, height), sprite} = &self.goal;
+        world.create_entity()
+            .with(Goal)
+            .with(BoundingBox(Rect::from_center(position.pick(&mut rng), *width, *height)))
+            .with(self.sprite(sprite, &mut assets))
+            .build();
+
+        let PlayerSpawn {position, size: (width, height), movement_speed, acceleration, friction, animations} = &self.player;
+        let player_animations = self.animation_state_machine(animations, Direction::Down, &mut assets);
+        let bounds = Rect::from_center(position.pick(&mut rng), *width, *height);
+        world.create_entity()
+            .with(Player)
+            .with(Collider)
+            .with(BoundingBox(bounds))
+            .with(Position::of(bounds))
+            .with(Velocity::default())
+            .with(locomotion(*movement_speed, *acceleration, *friction))
+            .with(initial_sprite(&player_animations))
+            .with(player_animations)
+            .build();
+
+        for enemy in &self.enemies {
+            let EnemySpawn {
+                position,
+                direction,
+                size: (width, height),
+                speed,
+                acceleration,
+                friction,
+                direction_change_delay_ms,
+                idle,
+                on_sight,
+                behaviour,
+                sight_radius,
+                memory_ms,
+                animations,
+            } = enemy;
+            let enemy_dir = match *direction {
+                SpawnDirection::Fixed(direction) => direction,
+                SpawnDirection::Random => match rng.gen_range(0..4) {
+                    0 => Direction::Up,
+                    1 => Direction::Down,
+                    2 => Direction::Left,
+                    3 => Direction::Right,
+                    _ => unreachable!(),
+                },
+            };
+            let enemy_animations = self.animation_state_machine(animations, enemy_dir, &mut assets);
+            let post = position.pick(&mut rng);
+            let bounds = Rect::from_center(post, *width, *height);
+            // Only wandering enemies start out walking; patrols head for their first waypoint
+            let heading = match idle {
+                IdleBehaviour::Wander => enemy_dir.into_vector(),
+                IdleBehaviour::Guard | IdleBehaviour::Patrol(_) => Vector::ZERO,
+            };
+            let mut enemy = world.create_entity()
+                .with(Enemy {
+                    idle: idle.clone(),
+                    reaction: *on_sight,
+                    sight_radius: *sight_radius,
+                    memory: Duration::from_millis(*memory_ms),
+                    post,
+                    mode: EnemyMode::Idle,
+                    last_seen: None,
+                    next_waypoint: 0,
+                    direction_changed: now,
+                    direction_change_delay: Duration::from_millis(*direction_change_delay_ms),
+                })
+                .with(Collider)
+                .with(BoundingBox(bounds))
+                .with(Position::of(bounds))
+                .with(Velocity::default())
+                .with(Locomotion {heading, ..locomotion(*speed, *acceleration, *friction)})
+                .with(initial_sprite(&enemy_animations))
+                .with(enemy_animations);
+            if let IdleBehaviour::Patrol(waypoints) = idle {
+                enemy = enemy.with(Route::new(waypoints[0].into()));
+            }
+            if let Some(tree) = behaviour.as_deref().and_then(|name| self.behaviour(name)?.tree.clone()) {
+                enemy = enemy
+                    .with(Behaviour(tree))
+                    .with(Blackboard::default());
+            }
+            enemy.build();
+        }
+
+        for ObstacleSpawn {position, size: (width, height), sprite, layer} in &self.obstacles {
+            let mut obstacle = world.create_entity()
+                .with(Solid)
+                .with(BoundingBox(Rect::from_center(position.pick(&mut rng), *width, *height)))
+                .with(*layer);
+            if let Some(sprite) = sprite {
+                obstacle = obstacle.with(self.sprite(sprite, &mut assets));
+            }
+            obstacle.build();
+        }
+
+        world.insert(rng);
+        world.insert(assets);
+    }
+
+    fn texture(&self, sheet: &str, assets: &mut AssetManager) -> TextureHandle {
+        let sprite_sheet = self.sprite_sheet(sheet).expect("sprite sheets are validated on load");
+        assets.texture(&sprite_sheet.path)
+    }
+
+    fn sprite(&self, SpriteRef {sheet, region: (x, y, width, height)}: &SpriteRef, assets: &mut AssetManager) -> Sprite {
+        Sprite {
+            texture: self.texture(sheet, assets),
+            region: Rect::new(*x, *y, *width, *height),
+        }
+    }
+
+    fn animation_state_machine(&self, animations: &AnimationsRef, facing: Direction, assets: &mut AssetManager) -> AnimationStateMachine {
+        let mut states = HashMap::new();
+        match animations {
+            AnimationsRef::Standard(WalkingAnimationsRef {sheet, top_left_frame: (x, y, width, height), frames_length, step_delay_ms}) => {
+                states.insert(AnimationState::Walk, DirectionalAnimations::standard_walking_animations(
+                    self.texture(sheet, assets),
+                    Rect::new(*x, *y, *width, *height),
+                    *frames_length,
+                    Duration::from_millis(*step_delay_ms),
+                ));
+            },
+            AnimationsRef::Named(NamedAnimationsRef {sheet, walking_up, walking_down, walking_left, walking_right, states: other_states}) => {
+                let texture = self.texture(sheet, assets);
+                let descriptor = self.sprite_sheet(sheet).and_then(|sprite_sheet| sprite_sheet.descriptor.as_ref())
+                    .expect("sprite sheet descriptors are validated on load");
+                let animation = |name: &str| descriptor.animation(name, texture)
+                    .expect("animation names are validated on load");
+                let directional = |[up, down, left, right]: [&String; 4]| DirectionalAnimations {
+                    up: animation(up),
+                    down: animation(down),
+                    left: animation(left),
+                    right: animation(right),
+                };
+                states.insert(AnimationState::Walk, directional([walking_up, walking_down, walking_left, walking_right]));
+                for (&state, names) in other_states {
+                    states.insert(state, directional(names.names()));
+                }
+            },
+        }
+        AnimationStateMachine::new(states, facing)
+    }
+
+    /// Checks everything that the file format itself cannot express
+    fn validate(&self) -> Result<(), String> {
+        let (world_width, world_height) = self.world_size;
+        if world_width == 0 || world_height == 0 {
+            return Err(format!("world_size must not be empty, got {:?}", self.world_size));
+        }
+        for (i, sprite_sheet) in self.sprite_sheets.iter().enumerate() {
+            if self.sprite_sheets[..i].iter().any(|other| other.name == sprite_sheet.name) {
+                return Err(format!("sprite sheet `{}` is defined more than once", sprite_sheet.name));
+            }
+        }
+        for (i, behaviour) in self.behaviours.iter().enumerate() {
+            if self.behaviours[..i].iter().any(|other| other.name == behaviour.name) {
+                return Err(format!("behaviour `{}` is defined more than once", behaviour.name));
+            }
+        }
+
+        let check_sheet = |context: &str, sheet: &str| match self.sprite_sheet(sheet) {
+            Some(_) => Ok(()),
+            None => Err(format!("{} uses unknown sprite sheet `{}`", context, sheet)),
+        };
+        let check_size = |context: &str, (width, height): (u32, u32)| match width > 0 && height > 0 {
+            true => Ok(()),
+            false => Err(format!("{} must have a non-empty size, got ({}, {})", context, width, height)),
+        };
+        let check_animations = |context: &str, animations: &AnimationsRef| match animations {
+            AnimationsRef::Standard(animations) => {
+                check_sheet(context, &animations.sheet)?;
+                let (_, _, width, height) = animations.top_left_frame;
+                check_size(context, (width, height))?;
+                match animations.frames_length {
+                    0 => Err(format!("{} must have at least one animation frame", context)),
+                    _ => Ok(()),
+                }
+            },
+            AnimationsRef::Named(animations) => {
+                check_sheet(context, &animations.sheet)?;
+                let descriptor = self.sprite_sheet(&animations.sheet).and_then(|sprite_sheet| sprite_sheet.descriptor.as_ref())
+                    .ok_or_else(|| format!("{} uses named animations but sprite sheet `{}` has no animations descriptor", context, animations.sheet))?;
+                if animations.states.contains_key(&AnimationState::Walk) {
+                    return Err(format!("{} must give its walking animations with walking_up, walking_down, walking_left and walking_right", context));
+                }
+                let walking = [&animations.walking_up, &animations.walking_down, &animations.walking_left, &animations.walking_right];
+                let others = animations.states.values().flat_map(AnimationNames::names);
+                for name in walking.into_iter().chain(others) {
+                    if !descriptor.animations.contains_key(name) {
+                        return Err(format!("{} uses unknown animation `{}` of sprite sheet `{}`", context, name, animations.sheet));
+                    }
+                }
+                // A looping animation would never let the entity out of a one-shot state
+                for (state, names) in animations.states.iter().filter(|(state, _)| state.plays_once()) {
+                    for name in names.names() {
+                        if descriptor.animations[name].loop_mode != LoopMode::Once {
+                            return Err(format!("{} plays animation `{}` of sprite sheet `{}` in state {:?}, which plays once, but the animation has loop_mode {:?}", context, name, animations.sheet, state, descriptor.animations[name].loop_mode));
+                        }
+                    }
+                }
+                Ok(())
+            },
+        };
+
+        let check_motion = |context: &str, speed: f32, acceleration: Option<f32>, friction: Option<f32>| {
+            if !(speed >= 0.0 && speed.is_finite()) {
+                return Err(format!("{} must have a finite speed of at least 0, got {}", context, speed));
+            }
+            match [acceleration, friction].into_iter().flatten().find(|&rate| rate.is_nan() || rate <= 0.0) {
+                Some(rate) => Err(format!("{} must have a positive acceleration and friction, got {}", context, rate)),
+                None => Ok(()),
+            }
+        };
+
+        check_size("goal", self.goal.size)?;
+        check_sheet("goal", &self.goal.sprite.sheet)?;
+        self.goal.position.validate("goal")?;
+
+        check_size("player", self.player.size)?;
+        check_animations("player", &self.player.animations)?;
+        check_motion("player", self.player.movement_speed, self.player.acceleration, self.player.friction)?;
+        self.player.position.validate("player")?;
+
+        for (i, enemy) in self.enemies.iter().enumerate() {
+            let context = format!("enemy #{}", i);
+            check_size(&context, enemy.size)?;
+            check_animations(&context, &enemy.animations)?;
+            check_motion(&context, enemy.speed, enemy.acceleration, enemy.friction)?;
+            enemy.position.validate(&context)?;
+            if enemy.idle == IdleBehaviour::Patrol(Vec::new()) {
+                return Err(format!("{} must patrol at least one waypoint", context));
+            }
+            if let Some(behaviour) = &enemy.behaviour {
+                if self.behaviour(behaviour).is_none() {
+                    return Err(format!("{} uses unknown behaviour `{}`", context, behaviour));
+                }
+            }
+            if enemy.on_sight != Reaction::Ignore && enemy.sight_radius == 0 {
+                return Err(format!("{} reacts to the player and must have a sight_radius greater than 0", context));
+            }
+        }
+
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            let context = format!("obstacle #{}", i);
+            check_size(&context, obstacle.size)?;
+            if let Some(sprite) = &obstacle.sprite {
+                check_sheet(&context, &sprite.sheet)?;
+            }
+            obstacle.position.validate(&context)?;
+        }
+        Ok(())
+    }
+}
+
+/// Enemies keep reacting to the player for two seconds after losing sight of them unless the level
+/// says otherwise
+fn default_memory_ms() -> u64 {
+    2000
+}
+
+/// The `Locomotion` of a character standing still, reaching its speed and stopping at once unless
+/// the level says otherwise
+fn locomotion(max_speed: f32, acceleration: Option<f32>, friction: Option<f32>) -> Locomotion {
+    Locomotion {
+        acceleration: acceleration.unwrap_or(f32::INFINITY),
+        friction: friction.unwrap_or(f32::INFINITY),
+        ..Locomotion::instant(max_speed)
+    }
+}
+
+/// The sprite shown until the `Animator` first runs
+fn initial_sprite(animations: &AnimationStateMachine) -> Sprite {
+    let animation = animations.animation(animations.state, animations.facing, Duration::ZERO)
+        .expect("characters always have walking animations, which they can stand idle with");
+    animation.frames[0].sprite.clone()
+}
+
+impl SpawnPosition {
+    fn pick(&self, rng: &mut impl Rng) -> Point {
+        match *self {
+            SpawnPosition::Fixed(x, y) => Point::new(x, y),
+            SpawnPosition::Random {x: (min_x, max_x), y: (min_y, max_y)} => Point::new(
+                rng.gen_range(min_x..=max_x),
+                rng.gen_range(min_y..=max_y),
+            ),
+        }
+    }
+
+    fn validate(&self, context: &str) -> Result<(), String> {
+        match *self {
+            SpawnPosition::Random {x: (min_x, max_x), y: (min_y, max_y)} if min_x > max_x || min_y > max_y => {
+                Err(format!("{} has an empty random position range", context))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PINK_FOREST: &str = include_str!("../assets/levels/pink_forest.ron");
+
+    #[test]
+    fn pink_forest_is_valid() {
+        Level::parse(PINK_FOREST, "pink_forest.ron").unwrap();
+    }
+
+    #[test]
+    fn one_shot_states_must_not_loop() {
+        let walking = r#"walking_right: "walk_right")"#;
+        assert!(PINK_FOREST.contains(walking));
+        let looping_attack = PINK_FOREST.replacen(walking, r#"walking_right: "walk_right", states: {Attack: Same("walk_down")})"#, 1);
+        let error = Level::parse(&looping_attack, "pink_forest.ron").unwrap_err();
+        assert!(matches!(&error, LevelError::Invalid {message, ..} if message.contains("loop_mode Loop")), "{}", error);
+    }
+
+    #[test]
+    fn animation_errors_name_the_missing_field() {
+        let named = r#"Named(sheet: "reaper", walking_up: "walk_up""#;
+        assert!(PINK_FOREST.contains(named));
+        let typo = PINK_FOREST.replacen(named, r#"Named(sheet: "reaper", walking_upp: "walk_up""#, 1);
+        let error = Level::parse(&typo, "pink_forest.ron").unwrap_err();
+        assert!(matches!(&error, LevelError::Parse {..}) && error.to_string().contains("missing field `walking_up` in `Named`"), "{}", error);
+    }
+}
@@ -0,0 +1,35 @@
+//! The components, systems and resources of a small top-down game built with `specs` and `sdl2`.
+//!
+//! A game is assembled with a [`GameBuilder`](game::GameBuilder), which registers the systems to
+//! run and the entities to spawn, and is then advanced one fixed timestep at a time with
+//! [`Game::step`](game::Game::step). Nothing but the [`renderer`], the [`hud`], the [`screens`]
+//! and the textures loaded by [`assets::LoadedTextures`] require a window, so games can also be
+//! simulated headless, e.g. in tests.
+
+#![warn(missing_docs)]
+
+/// Directions of motion
+pub mod direction;
+pub mod vector;
+/// The components attached to entities of the world
+pub mod components;
+/// The resources shared by the systems of the world
+pub mod resources;
+/// The systems updating the world at every fixed timestep
+pub mod systems;
+pub mod renderer;
+pub mod hud;
+pub mod input;
+pub mod states;
+pub mod screens;
+pub mod game;
+pub mod level;
+pub mod headless;
+pub mod replay;
+pub mod spatial_grid;
+pub mod pathfinding;
+pub mod behaviour_tree;
+pub mod camera;
+pub mod assets;
+pub mod sprite_sheet;
+pub mod tilemap;
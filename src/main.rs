@@ -1,253 +1,581 @@
-mod direction;
-mod components;
-mod resources;
-mod systems;
-mod renderer;
 mod sdl_context;
 
-use std::ops::ControlFlow;
-use std::thread;
 use std::error::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::{Instant, Duration};
 
-use rand::{Rng, thread_rng};
 use sdl2::{
+    GameControllerSubsystem,
+    controller::GameController,
     event::Event,
-    keyboard::Keycode,
     pixels::Color,
-    rect::{Point, Rect},
-    image::LoadTexture,
 };
+use specs::{WorldExt, SystemData, ReadStorage, Join};
+
+use learning_game_in_rust::game::{Game, GameBuilder, FIXED_TIMESTEP};
+use learning_game_in_rust::level::Level;
+use learning_game_in_rust::replay::{Recording, ReplayError};
+use learning_game_in_rust::headless::InputScript;
+use learning_game_in_rust::resources::{KeyboardEvent, GameStatus, GameTime, InterpolationAlpha};
+use learning_game_in_rust::components::{Player, BoundingBox};
+use learning_game_in_rust::renderer::{RendererData, PathOverlayData};
+use learning_game_in_rust::states::{GameState, StateHooks, StateStack};
+use learning_game_in_rust::screens::render_screen;
+use learning_game_in_rust::hud::{Hud, Anchor, TextStyle};
+use learning_game_in_rust::input::{Action, InputConfig, InputState};
+use learning_game_in_rust::camera::Camera;
+use learning_game_in_rust::pathfinding::PathSearch;
+use learning_game_in_rust::assets::{AssetManager, LoadedTextures};
 use sdl_context::SDLGameContext;
-use specs::{World, WorldExt, Builder, DispatcherBuilder, SystemData};
-
-use crate::direction::Direction;
-use crate::resources::{TimeDelta, KeyboardEvent, GameStatus};
-use crate::components::{
-    BoundingBox,
-    Velocity,
-    Sprite,
-    MovementAnimations,
-    Player,
-    Enemy,
-    Goal,
-};
-use crate::renderer::RendererData;
+
+/// The level played when `--level` is not given
+const DEFAULT_LEVEL: &str = "assets/levels/pink_forest.ron";
+/// The key bindings used when `--input` is not given
+const DEFAULT_INPUT_CONFIG: &str = "assets/input.ron";
+/// Number of ticks simulated by `--headless` when `--ticks` is not given (one minute of game time)
+const DEFAULT_HEADLESS_TICKS: u64 = 60 * 60;
+
+/// Options given on the command line
+///
+/// Usage: `learning_game_in_rust [--level FILE] [--input FILE] [--seed N]
+///     [--record FILE | --replay FILE] [--headless] [--ticks N] [--script 0:right,30:stop]
+///     [--jump-point-search]`
+#[derive(Debug)]
+struct CliOptions {
+    /// The level file to play
+    level: PathBuf,
+    /// The input config file with the key bindings
+    input: PathBuf,
+    /// The seed of the random number generator, overriding the seed of the level
+    seed: Option<u64>,
+    /// Where to save a recording of the first game of the session. Later games are saved to
+    /// numbered files next to it, see `Recorder`.
+    record: Option<PathBuf>,
+    /// A recording to replay instead of reading the keyboard
+    replay: Option<PathBuf>,
+    /// Run the simulation without opening a window
+    headless: bool,
+    /// Number of ticks to simulate in headless mode
+    ticks: Option<u64>,
+    /// Scripted input for headless mode
+    script: InputScript,
+    /// How paths are searched
+    path_search: PathSearch,
+}
+
+impl CliOptions {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            level: PathBuf::from(DEFAULT_LEVEL),
+            input: PathBuf::from(DEFAULT_INPUT_CONFIG),
+            seed: None,
+            record: None,
+            replay: None,
+            headless: false,
+            ticks: None,
+            script: InputScript::default(),
+            path_search: PathSearch::AStar,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--level" => {
+                    options.level = args.next().ok_or("Missing value for --level")?.into();
+                },
+                "--input" => {
+                    options.input = args.next().ok_or("Missing value for --input")?.into();
+                },
+                "--seed" => {
+                    let seed = args.next().ok_or("Missing value for --seed")?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("Invalid seed `{}`", seed))?);
+                },
+                "--record" => {
+                    options.record = Some(args.next().ok_or("Missing value for --record")?.into());
+                },
+                "--replay" => {
+                    options.replay = Some(args.next().ok_or("Missing value for --replay")?.into());
+                },
+                "--headless" => options.headless = true,
+                "--ticks" => {
+                    let ticks = args.next().ok_or("Missing value for --ticks")?;
+                    options.ticks = Some(ticks.parse().map_err(|_| format!("Invalid number of ticks `{}`", ticks))?);
+                },
+                "--script" => {
+                    options.script = args.next().ok_or("Missing value for --script")?.parse()?;
+                },
+                "--jump-point-search" => options.path_search = PathSearch::JumpPoint,
+                other => return Err(format!("Unknown argument `{}`", other)),
+            }
+        }
+        if options.record.is_some() && options.replay.is_some() {
+            return Err("--record and --replay cannot be used together".to_string());
+        }
+        Ok(options)
+    }
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    
+    let mut options = CliOptions::parse(std::env::args().skip(1))?;
+    let replay = match &options.replay {
+        Some(path) => Some(Recording::load(path)?),
+        None => None,
+    };
+    // A replay must be played on the level and with the seed it was recorded with
+    if let Some(recording) = &replay {
+        options.level = recording.level.clone();
+        options.seed = Some(recording.seed);
+    }
+    let level = Level::load(&options.level)?;
+    if options.headless {
+        return run_headless(level, options, replay);
+    }
+
+    let input_config = InputConfig::load(&options.input)?;
     let sdl_context = sdl_context::sld_context();
 
     let texture_creator = sdl_context.canvas.texture_creator();
-    let error = String::from("Could not load properly textures");
-    let textures = vec!(
-        texture_creator.load_texture("assets/bardo_2x.png").expect(error.as_str()),
-        texture_creator.load_texture("assets/reaper_blade_2x.png").expect(error.as_str()),
-        texture_creator.load_texture("assets/pinktrees_2x.png").expect(error.as_str()),
-    );
-
-    let mut dispatcher = DispatcherBuilder::new()
-        .with(systems::Keyboard, "Keyboard", &[])
-        .with(systems::AI, "AI", &[])
-        .with(systems::Movement {world_bounds: sdl_context.world_bounds}, "Movement", &["Keyboard", "AI"])
-        .with(systems::WinLoseChecker, "WinLoseChecker", &["Movement"])
-        .with(systems::Animator, "Animator", &["Keyboard", "AI"])
+    let mut textures = LoadedTextures::new(&texture_creator);
+
+    let level_name = level.name.clone();
+    let mut game = game_builder(&level, &options)
+        .with_level(level)
         .build();
+    println!("Seed: {}", game.seed());
+    RendererData::setup(game.world_mut());
+    game.world_mut().write_resource::<Camera>().viewport = sdl_context.canvas.output_size()?;
+    // Missing images are reported before the window shows anything
+    if let Some(error) = textures.load_new(&game.world().read_resource::<AssetManager>()).into_iter().next() {
+        return Err(error.into());
+    }
+
+    let mut recorder = options.record.as_ref().map(|path| Recorder::new(path, &options.level, game.seed()));
+    let session = Session::new(game, level_name, recorder.as_mut());
+    game_loop(sdl_context, session, textures, &input_config, replay.as_ref())?;
+
+    if let Some(recorder) = &recorder {
+        recorder.save()?;
+    }
+    Ok(())
+}
 
-    let mut world = World::new();
-    dispatcher.setup(&mut world);
-    RendererData::setup(&mut world);
-    
-    generate_goal_in_world(&mut world, &sdl_context);
-    generate_player_in_world(&mut world, &sdl_context);
-    generate_enemies_in_world(&mut world, &sdl_context);
+/// Records the games of a session. A recording only ever covers a single game, so the first game
+/// is saved to the path given with `--record` and each game after it to a numbered file next to
+/// it, e.g. `run-2.ron`.
+struct Recorder {
+    path: PathBuf,
+    recording: Recording,
+    /// The number of the game being recorded, starting at 1
+    game: u32,
+}
 
-    world.insert(TimeDelta::default());
-    world.insert(GameStatus::Running);
+impl Recorder {
+    fn new(path: &Path, level: &Path, seed: u64) -> Self {
+        Self {path: path.to_path_buf(), recording: Recording::new(level, seed), game: 1}
+    }
 
-    game_loop(sdl_context, world, dispatcher, textures)?;
+    /// The file the game being recorded is saved to
+    fn game_path(&self) -> PathBuf {
+        if self.game == 1 {
+            return self.path.clone();
+        }
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match self.path.extension() {
+            Some(extension) => format!("{}-{}.{}", stem, self.game, extension.to_string_lossy()),
+            None => format!("{}-{}", stem, self.game),
+        };
+        self.path.with_file_name(file_name)
+    }
 
+    /// Saves the game recorded so far
+    fn save(&self) -> Result<(), ReplayError> {
+        let path = self.game_path();
+        self.recording.save(&path)?;
+        println!("Recording saved to {}", path.display());
+        Ok(())
+    }
+
+    /// Saves the game that just ended and starts recording the next one, played with the given
+    /// seed
+    fn next_game(&mut self, seed: u64) {
+        if let Err(error) = self.save() {
+            eprintln!("{}", error);
+        }
+        self.recording = Recording::new(self.recording.level.clone(), seed);
+        self.game += 1;
+    }
+}
+
+/// A builder for the given level with the options of the command line applied
+fn game_builder(level: &Level, options: &CliOptions) -> GameBuilder {
+    let builder = GameBuilder::new(level.world_bounds()).with_path_search(options.path_search);
+    match options.seed {
+        Some(seed) => builder.with_seed(seed),
+        None => builder,
+    }
+}
+
+/// RUN GAME WITHOUT WINDOW
+fn run_headless(level: Level, options: CliOptions, replay: Option<Recording>) -> Result<(), Box<dyn Error>> {
+    let mut game = game_builder(&level, &options)
+        .with_level(level)
+        .build();
+    println!("Seed: {}", game.seed());
+    let status = match &replay {
+        Some(recording) => recording.replay(&mut game),
+        None => game.run(options.ticks.unwrap_or(DEFAULT_HEADLESS_TICKS), &options.script),
+    };
+    println!("Headless run finished after {} ticks: {:?}", game.tick(), status);
+    if let Some(path) = &options.record {
+        let recording = Recording {
+            level: options.level.clone(),
+            seed: game.seed(),
+            ticks: game.tick(),
+            inputs: options.script.clone(),
+            time_scales: Vec::new(),
+        };
+        recording.save(path)?;
+        println!("Recording saved to {}", path.display());
+    }
+    let (players, bounding_boxes) = game.world().system_data::<(ReadStorage<Player>, ReadStorage<BoundingBox>)>();
+    for (_, BoundingBox(bounds)) in (&players, &bounding_boxes).join() {
+        println!("Player position: ({}, {})", bounds.center().x(), bounds.center().y());
+    }
     Ok(())
 }
 
-fn game_loop(mut sdl_context: SDLGameContext, mut world: World, mut dispatcher: specs::Dispatcher, textures: Vec<sdl2::render::Texture>) -> Result<(), Box<dyn Error>> {
-    let frame_duration = Duration::from_nanos(1_000_000_000 / 60);
+/// The maximum amount of real time accounted for in a single frame. Without this, a very slow
+/// frame (e.g. while the window is being dragged) would queue up so many updates that the game
+/// could never catch up again.
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// How often the files of the loaded textures are checked for changes
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The slowest and fastest time scales reachable with the debug controls
+const TIME_SCALE_RANGE: (f64, f64) = (1.0 / 16.0, 4.0);
+
+/// The number of games that can be lost before going back to the title screen
+const LIVES: u32 = 3;
+/// The points scored by winning a game, on top of `TIME_BONUS`
+const VICTORY_POINTS: u32 = 1000;
+/// The points scored by winning a game for every second left of `TIME_BONUS_LIMIT`
+const TIME_BONUS: u32 = 10;
+/// How long a game can last and still score a `TIME_BONUS`
+const TIME_BONUS_LIMIT: Duration = Duration::from_secs(60);
+/// The distance between the HUD and the edges of the window, in pixels
+const HUD_MARGIN: u32 = 16;
+
+/// The game played in the window along with what the game loop keeps between frames. Reacts to
+/// the changes of `GameState`.
+struct Session<'a> {
+    game: Game,
+    level_name: String,
+    recorder: Option<&'a mut Recorder>,
+    /// The movement keys being held
+    input: InputState,
+    /// The input waiting for the next update of the world
+    pending_event: Option<KeyboardEvent>,
+    /// The number of games that can still be lost
+    lives: u32,
+    /// The points scored by the games won since the title screen
+    score: u32,
+    /// Whether the paths found by the `Pathfinder` are drawn over the world
+    show_paths: bool,
+}
+
+impl<'a> Session<'a> {
+    fn new(game: Game, level_name: String, recorder: Option<&'a mut Recorder>) -> Self {
+        Self {game, level_name, recorder, input: InputState::default(), pending_event: None, lives: LIVES, score: 0, show_paths: false}
+    }
+
+    /// Starts a new game on the same level. The game that just ended is saved if it was
+    /// recorded, and the new one is recorded to its own file.
+    fn restart(&mut self) {
+        self.game.restart();
+        if let Some(recorder) = self.recorder.as_deref_mut() {
+            recorder.next_game(self.game.seed());
+        }
+    }
+}
+
+impl StateHooks for Session<'_> {
+    fn on_enter(&mut self, state: GameState) {
+        match state {
+            GameState::Title => {
+                self.lives = LIVES;
+                self.score = 0;
+            },
+            // Keys pressed on another screen are not meant for the world, but the keys still held
+            // should move the player of a new game right away
+            GameState::Playing => {
+                self.pending_event = None;
+                self.input.report_again();
+            },
+            GameState::Paused => {},
+            GameState::GameOver => self.lives = self.lives.saturating_sub(1),
+            GameState::Victory => {
+                let elapsed = self.game.world().read_resource::<GameTime>().0;
+                let seconds_left = TIME_BONUS_LIMIT.saturating_sub(elapsed).as_secs() as u32;
+                self.score += VICTORY_POINTS + seconds_left * TIME_BONUS;
+            },
+        }
+    }
+}
+
+/// The lines of text shown by the screen of the given state
+fn screen_lines(session: &Session, state: GameState) -> Vec<String> {
+    match state {
+        GameState::Title => vec![session.level_name.clone(), "Press Enter to start".to_string()],
+        GameState::Playing => Vec::new(),
+        GameState::Paused => vec!["Paused".to_string(), "P to resume - R to restart".to_string()],
+        GameState::GameOver if session.lives == 0 => vec![
+            "Game over".to_string(),
+            format!("Score {}", session.score),
+            "Press Enter".to_string(),
+        ],
+        GameState::GameOver => vec![
+            "You lose!".to_string(),
+            format!("{} {} left", session.lives, if session.lives == 1 { "life" } else { "lives" }),
+            "Press Enter to try again".to_string(),
+        ],
+        GameState::Victory => vec![
+            "You win!".to_string(),
+            format!("Score {}", session.score),
+            "Press Enter to play again".to_string(),
+        ],
+    }
+}
+
+/// The HUD shown over the world in the given state
+fn hud(session: &Session, state: GameState) -> Hud {
+    let mut hud = Hud::new(HUD_MARGIN);
+    // The title screen shows the world without giving anything away
+    if state == GameState::Title {
+        return hud;
+    }
+    let style = TextStyle::default();
+    let elapsed = session.game.world().read_resource::<GameTime>().0.as_secs();
+    hud.text(Anchor::TopLeft, session.level_name.as_str(), style)
+        .text(Anchor::TopLeft, format!("Time {}:{:02}", elapsed / 60, elapsed % 60), style)
+        .text(Anchor::TopRight, format!("Score {}", session.score), style)
+        .text(Anchor::TopRight, format!("Lives {}", session.lives), style);
+    let time_scale = session.game.time_scale();
+    if time_scale != 1.0 {
+        hud.text(Anchor::BottomRight, format!("Speed x{}", time_scale), style);
+    }
+    hud
+}
+
+/// Runs the game of the session in the window until it is closed, or until the end of `replay`
+/// when given. The input of the world comes from the keyboard, or from `replay`. Every input
+/// delivered to the world is added to the recording of the session, if any.
+fn game_loop(
+    mut sdl_context: SDLGameContext,
+    mut session: Session,
+    mut textures: LoadedTextures,
+    input_config: &InputConfig,
+    replay: Option<&Recording>,
+) -> Result<(), Box<dyn Error>> {
     let mut event_pump = sdl_context.context.event_pump()?;
-    Ok('running: loop {
+    let controller_subsystem = sdl_context.context.game_controller()?;
+    let mut controllers = HashMap::new();
+
+    // A replay has no one to press a key on the title screen
+    let initial_state = if replay.is_some() { GameState::Playing } else { GameState::Title };
+    let mut states = StateStack::new(initial_state, &mut session);
+
+    // Fixed timestep with an accumulator: the real time elapsed since the last frame is added to
+    // the accumulator and the world is then updated in steps of exactly `FIXED_TIMESTEP` until
+    // less than one step remains. The leftover time is used to interpolate the rendering between
+    // the previous and the current state of the world.
+    //
+    // For more information:
+    // * http://web.archive.org/web/20190506122532/http://gafferongames.com/post/fix_your_timestep/
+    let mut previous_frame = Instant::now();
+    let mut accumulator = Duration::ZERO;
+    let mut last_reload_check = Instant::now();
+    'running: loop {
+        let now = Instant::now();
+        accumulator += (now - previous_frame).min(MAX_FRAME_TIME);
+        previous_frame = now;
+
         // Handle events
-        let keyboard_event = handle_game_events(&mut event_pump);
-        if keyboard_event == Some(KeyboardEvent::Escape){
-            break 'running;
+        let mut actions = handle_game_events(&mut event_pump, &controller_subsystem, &mut controllers, input_config, &mut session.input);
+        // Clicking walks the player to the point clicked while playing, and confirms otherwise
+        let clicked = match (session.input.take_pointer(), states.current()) {
+            (Some((point, _)), GameState::Playing) => Some(point),
+            (Some((_, true)), _) => {
+                actions.push(Action::Confirm);
+                None
+            },
+            _ => None,
+        };
+        let mut step_once = false;
+        for action in actions {
+            match (action, states.current()) {
+                (Action::Quit, _) => break 'running,
+                (Action::Confirm, GameState::Title) => states.switch(GameState::Playing, &mut session),
+                // Out of lives, everything starts over from the title screen
+                (Action::Confirm, GameState::GameOver) if session.lives == 0 => {
+                    session.restart();
+                    states.reset(GameState::Title, &mut session);
+                },
+                (Action::Confirm, GameState::GameOver | GameState::Victory) |
+                (Action::Restart, GameState::Paused) => {
+                    session.restart();
+                    states.reset(GameState::Playing, &mut session);
+                },
+                (Action::Pause, GameState::Playing) => states.push(GameState::Paused, &mut session),
+                (Action::Pause, GameState::Paused) => {
+                    states.pop(&mut session);
+                },
+                (Action::Step, GameState::Paused) => step_once = true,
+                (Action::TogglePaths, _) => session.show_paths = !session.show_paths,
+                (Action::SlowDown | Action::SpeedUp | Action::ResetTimeScale, _) => {
+                    change_time_scale(&mut session.game, action, replay.is_some());
+                },
+                _ => {},
+            }
+        }
+        // Outside of the playing state, the world is frozen and does not accumulate time, except
+        // for exactly one update when stepping a paused game
+        if !states.current().is_simulated() {
+            accumulator = if step_once { FIXED_TIMESTEP } else { Duration::ZERO };
+        }
+        // The event is consumed by the next update of the world, which may only happen in a
+        // later frame if this one did not accumulate enough time.
+        let keyboard_event = session.input.take_event();
+        if keyboard_event.is_some() {
+            session.pending_event = keyboard_event;
+        }
+        if let Some(point) = clicked {
+            let world = session.game.world();
+            let InterpolationAlpha(alpha) = *world.read_resource();
+            let destination = world.read_resource::<Camera>().screen_to_world(point, alpha);
+            session.pending_event = Some(KeyboardEvent::MoveTo {x: destination.x(), y: destination.y()});
         }
-        world.insert(keyboard_event);
 
         // Update world
-        if let ControlFlow::Break(_) = update_world(&mut world, frame_duration, &mut dispatcher) {
-            break;
+        while accumulator >= FIXED_TIMESTEP {
+            accumulator -= FIXED_TIMESTEP;
+            let game = &mut session.game;
+            let tick = game.tick();
+            let keyboard_event = match replay {
+                Some(replay) if replay.is_finished(tick) => break 'running,
+                Some(replay) => replay.event_at(tick),
+                None => session.pending_event.take(),
+            };
+            if let Some(time_scale) = replay.and_then(|replay| replay.time_scale_at(tick)) {
+                game.set_time_scale(time_scale);
+            }
+            if let Some(Recorder {recording, ..}) = session.recorder.as_deref_mut() {
+                recording.record(tick, keyboard_event);
+                recording.record_time_scale(tick, game.time_scale());
+            }
+            let end_state = match game.step(keyboard_event) {
+                GameStatus::Running => continue,
+                GameStatus::Win => GameState::Victory,
+                GameStatus::Lose => GameState::GameOver,
+            };
+            states.reset(end_state, &mut session);
+            accumulator = Duration::ZERO;
         }
 
-        // Render game
-        render_game(&mut sdl_context, &world, &textures)?;
+        // Load textures registered by the last updates and reload the ones edited on disk. A
+        // texture that cannot be loaded is reported and left out, rather than ending the game.
+        for error in textures.load_new(&session.game.world().read_resource::<AssetManager>()) {
+            eprintln!("{}", error);
+        }
+        if last_reload_check.elapsed() >= HOT_RELOAD_INTERVAL {
+            last_reload_check = Instant::now();
+            for reloaded in textures.reload_modified() {
+                match reloaded {
+                    Ok(path) => println!("Reloaded texture {}", path.display()),
+                    Err(error) => eprintln!("{}", error),
+                }
+            }
+        }
 
-        // LIMIT FRAMERATE
+        // Render game
+        // A frozen world shows its latest state, so that each step can be seen as it happens
+        let alpha = if states.current().is_simulated() {
+            accumulator.as_secs_f64() / FIXED_TIMESTEP.as_secs_f64()
+        } else {
+            1.0
+        };
+        *session.game.world_mut().write_resource() = InterpolationAlpha(alpha);
+        render_game(&mut sdl_context, &session, &textures, states.current())?;
+    }
+    Ok(())
+}
 
-        // Manage the timing of the game so that the loop doesn't go too quickly or too slowly.
-        //
-        // Time stepping is a complex topic. We're simplifying things by just always assuming that
-        // 1/60 seconds has passed in each iteration of the loop. 1/60th of a second is 60 FPS.
-        // There are *many* downsides to the code as it is below, but it's good enough as a
-        // starting point.
-        //
-        // For more information and some more robust approaches:
-        // * http://web.archive.org/web/20190506122532/http://gafferongames.com/post/fix_your_timestep/
-        // * https://www.gamasutra.com/blogs/BramStolk/20160408/269988/Fixing_your_time_step_the_easy_way_with_the_golden_48537_ms.php
-        thread::sleep(frame_duration);
-    })
+/// Applies a time scale command of the debug controls
+fn change_time_scale(game: &mut Game, action: Action, replaying: bool) {
+    // The time scale changes the simulation, so a replay must keep the recorded one
+    if replaying {
+        println!("The time scale cannot be changed during a replay");
+        return;
+    }
+    let time_scale = match action {
+        Action::SlowDown => game.time_scale() / 2.0,
+        Action::SpeedUp => game.time_scale() * 2.0,
+        Action::ResetTimeScale => 1.0,
+        _ => return,
+    };
+    let (min, max) = TIME_SCALE_RANGE;
+    game.set_time_scale(time_scale.clamp(min, max));
+    println!("Time scale: {}", game.time_scale());
 }
 
 /// RENDER GAME IN WINDOW
-fn render_game(sdl_context: &mut SDLGameContext, world: &World, textures: &Vec<sdl2::render::Texture>) -> Result<(), Box<dyn Error>> {
+fn render_game(sdl_context: &mut SDLGameContext, session: &Session, textures: &LoadedTextures, state: GameState) -> Result<(), Box<dyn Error>> {
     sdl_context.canvas.set_draw_color(Color::RGB(128, 128, 128));
     sdl_context.canvas.clear();
-    let renderer_data: RendererData = world.system_data();
+    let renderer_data: RendererData = session.game.world().system_data();
     renderer_data.render(&mut sdl_context.canvas, textures)?;
+    if session.show_paths {
+        let path_overlay: PathOverlayData = session.game.world().system_data();
+        path_overlay.render(&mut sdl_context.canvas)?;
+    }
+    hud(session, state).render(&mut sdl_context.canvas)?;
+    render_screen(&mut sdl_context.canvas, state, &screen_lines(session, state))?;
     sdl_context.canvas.present();
     Ok(())
 }
 
-/// UPDATE GAME
-fn update_world(world: &mut World, frame_duration: Duration, dispatcher: &mut specs::Dispatcher) -> ControlFlow<()> {
-    *world.write_resource() = TimeDelta(frame_duration);
-    dispatcher.dispatch(&*world);
-    world.maintain();
-    if let ControlFlow::Break(_) = check_win_or_lose(&*world) {
-        return ControlFlow::Break(());
-    }
-    ControlFlow::Continue(())
-}
-
 /// HANDLE GAME EVENTS
-fn handle_game_events(event_pump: &mut sdl2::EventPump) -> Option<KeyboardEvent> {
-    let mut keyboard_event = None;
+///
+/// Keeps track of the movement keys, buttons and sticks in `input` and returns the other actions
+/// triggered since the last call. Game controllers are opened as they are plugged in and closed as
+/// they are unplugged.
+fn handle_game_events(
+    event_pump: &mut sdl2::EventPump,
+    controller_subsystem: &GameControllerSubsystem,
+    controllers: &mut HashMap<u32, GameController>,
+    config: &InputConfig,
+    input: &mut InputState,
+) -> Vec<Action> {
+    let mut actions = Vec::new();
     for event in event_pump.poll_iter() {
         match event {
-            Event::Quit {..} |
-            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                keyboard_event = Some(KeyboardEvent::Escape)
-            },
-            Event::KeyDown { keycode: Some(Keycode::Up), repeat: false, .. } => {
-                keyboard_event = Some(KeyboardEvent::MoveInDirection(Direction::Up));
-            },
-            Event::KeyDown { keycode: Some(Keycode::Down), repeat: false, .. } => {
-                keyboard_event = Some(KeyboardEvent::MoveInDirection(Direction::Down));
-            },
-            Event::KeyDown { keycode: Some(Keycode::Left), repeat: false, .. } => {
-                keyboard_event = Some(KeyboardEvent::MoveInDirection(Direction::Left));
+            // Also sent at startup for the game controllers already plugged in
+            Event::ControllerDeviceAdded { which: joystick_index, .. } => {
+                match controller_subsystem.open(joystick_index) {
+                    Ok(controller) => {
+                        println!("Game controller connected: {}", controller.name());
+                        controllers.insert(controller.instance_id(), controller);
+                    },
+                    Err(error) => eprintln!("Could not open game controller {}: {}", joystick_index, error),
+                }
             },
-            Event::KeyDown { keycode: Some(Keycode::Right), repeat: false, .. } => {
-                keyboard_event = Some(KeyboardEvent::MoveInDirection(Direction::Right));
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = controllers.remove(&which) {
+                    println!("Game controller disconnected: {}", controller.name());
+                }
             },
-            Event::KeyUp { keycode: Some(Keycode::Left), repeat: false, .. } |
-            Event::KeyUp { keycode: Some(Keycode::Right), repeat: false, .. } |
-            Event::KeyUp { keycode: Some(Keycode::Up), repeat: false, .. } |
-            Event::KeyUp { keycode: Some(Keycode::Down), repeat: false, .. } => {
-                keyboard_event = Some(KeyboardEvent::Stop);
-            },
-            _ => {}
-        }
-    }
-    keyboard_event
-}
-
-fn check_win_or_lose(world: &World) -> ControlFlow<()> {
-    match *world.read_resource() {
-        GameStatus::Running => {}, // Keep going
-        GameStatus::Win => {
-            println!("You win!");
-            return ControlFlow::Break(());
-        },
-        GameStatus::Lose => {
-            println!("You lose!");
-            return ControlFlow::Break(());
-        },
-    }
-    ControlFlow::Continue(())
-}
-
-fn generate_goal_in_world(world: &mut World, sdl_context: &SDLGameContext){
-    let mut rng = thread_rng();
-    let position_error = "Error generating positions of goal";
-    let random_x_position = rng.gen_range(-i32::try_from(sdl_context.width/2).expect(position_error)..i32::try_from(sdl_context.width/2).expect(position_error));
-    let y_position = -i32::try_from((sdl_context.height/2)-116).expect(position_error);
-    world.create_entity()
-        .with(Goal)
-        .with(BoundingBox(Rect::from_center((random_x_position, y_position), 92, 116)))
-        .with(Sprite {
-            texture_id: sdl_context.pink_tree_texture,
-            region: Rect::new(0, 0, 128, 128),
-        })
-        .build();
-}
-
-fn generate_player_in_world(world: &mut World, sdl_context: &SDLGameContext){
-    let mut rng = thread_rng();
-    let position_error = "Error generating positions of player";
-    let player_animations = MovementAnimations::standard_walking_animations(
-        sdl_context.bardo_texture,
-        Rect::new(0, 0, 52, 72),
-        3,
-        Duration::from_millis(150),
-    );
-    let random_x_position = rng.gen_range(-i32::try_from(sdl_context.width/2).expect(position_error)..i32::try_from(sdl_context.width/2).expect(position_error));
-    world.create_entity()
-        .with(Player {movement_speed: 200})
-        .with(BoundingBox(Rect::from_center((random_x_position, 250), 32, 58)))
-        .with(Velocity {speed: 0, direction: Direction::Down})
-        .with(player_animations.animation_for(Direction::Down).frames[0].sprite.clone())
-        .with(player_animations.animation_for(Direction::Down).clone())
-        .with(player_animations)
-        .build();
-}
-
-/// Generate enemies in random positions. To avoid overlap with anything else, an area of the
-/// world coordinate system is divided up into a 2D grid. Each enemy gets a random position
-/// within one of the cells of that grid.
-fn generate_enemies_in_world(world: &mut World, sdl_context: &SDLGameContext){
-    let mut rng = thread_rng();
-    for i in -1..2 {
-        for j in -2..0 {
-            let enemy_pos = Point::new(
-                i * 200 + rng.gen_range(-80..80),
-                j * 140 + 200 + rng.gen_range(-40..40),
-            );
-            let enemy_dir = match rng.gen_range(0..4) {
-                0 => Direction::Up,
-                1 => Direction::Down,
-                2 => Direction::Left,
-                3 => Direction::Right,
-                _ => unreachable!(),
-            };
-            generate_ennemy_in_world(world, enemy_pos, enemy_dir, &sdl_context);
+            _ => {},
         }
+        actions.extend(input.handle_event(&event, config));
     }
+    actions
 }
-
-fn generate_ennemy_in_world(world: &mut World, enemy_pos: Point, enemy_dir: Direction, sdl_context: &SDLGameContext) {
-    let enemy_animations = MovementAnimations::standard_walking_animations(
-        sdl_context.reaper_texture,
-        Rect::new(0, 0, 64, 72),
-        3,
-        Duration::from_millis(150),
-    );
-    world.create_entity()
-        .with(Enemy {
-            direction_timer: Instant::now(),
-            direction_change_delay: Duration::from_millis(200),
-        })
-        .with(BoundingBox(Rect::from_center(enemy_pos, 50, 58)))
-        .with(Velocity {speed: 200, direction: enemy_dir})
-        .with(enemy_animations.animation_for(enemy_dir).frames[0].sprite.clone())
-        .with(enemy_animations.animation_for(enemy_dir).clone())
-        .with(enemy_animations.clone())
-        .build();
-}
\ No newline at end of file
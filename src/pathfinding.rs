@@ -0,0 +1,591 @@
+//! Finding a way through the world around its obstacles. The [`NavGrid`] divides the world into
+//! square cells and keeps track of the obstacles overlapping each of them. A cell is walkable by an
+//! entity of a given size when the entity can stand in its center without touching an obstacle or
+//! leaving the world. The [`Pathfinder`] resource searches paths from cell center to cell center,
+//! moving in the four directions entities can move in, and remembers them for the next entity
+//! going the same way.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+
+use sdl2::rect::{Point, Rect};
+use specs::{World, ReadStorage, Join};
+
+use crate::tilemap::TileMap;
+use crate::components::{BoundingBox, Solid};
+
+/// The width and height of the cells paths are made of, in world coordinates
+pub const PATH_CELL_SIZE: u32 = 15;
+
+/// The space kept between an entity following a path and the obstacles along the way, so that
+/// overshooting a turn by a few pixels does not run it into a wall
+const CLEARANCE: u32 = 4;
+
+/// The number of searches the `Pathfinder` remembers. Once full, it forgets all of them at once.
+const PATH_CACHE_CAPACITY: usize = 256;
+
+/// The number of paths kept for `Pathfinder::recent_paths`
+const RECENT_PATH_COUNT: usize = 16;
+
+/// The column and row of a cell of a `NavGrid`. Signed so that the neighbours of the cells on the
+/// edges can be looked at, outside of the grid.
+type Cell = (i32, i32);
+
+/// The four moves from a cell to its neighbours
+const MOVES: [Cell; 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+/// The obstacles of a world laid out on a grid of cells of `PATH_CELL_SIZE`
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    world_bounds: Rect,
+    columns: i32,
+    rows: i32,
+    obstacles: Vec<Rect>,
+    /// The indices in `obstacles` of the obstacles overlapping each cell, row by row
+    cell_obstacles: Vec<Vec<usize>>,
+}
+
+impl NavGrid {
+    /// A grid covering the given area of the world, with the given obstacles
+    pub fn new(world_bounds: Rect, obstacles: impl IntoIterator<Item = Rect>) -> Self {
+        let columns = (world_bounds.width() / PATH_CELL_SIZE).max(1) as i32;
+        let rows = (world_bounds.height() / PATH_CELL_SIZE).max(1) as i32;
+        let mut grid = Self {
+            world_bounds,
+            columns,
+            rows,
+            obstacles: Vec::new(),
+            cell_obstacles: vec![Vec::new(); (columns * rows) as usize],
+        };
+        for obstacle in obstacles {
+            for cell in grid.cells_overlapping(obstacle) {
+                let index = grid.index(cell);
+                grid.cell_obstacles[index].push(grid.obstacles.len());
+            }
+            grid.obstacles.push(obstacle);
+        }
+        grid
+    }
+
+    /// A grid covering the given area of the world, with the `Solid` entities and the solid tiles
+    /// of the `TileMap` of the world as obstacles
+    pub fn from_world(world: &World, world_bounds: Rect) -> Self {
+        let (solids, bounding_boxes) = world.system_data::<(ReadStorage<Solid>, ReadStorage<BoundingBox>)>();
+        let mut obstacles: Vec<Rect> = (&solids, &bounding_boxes).join()
+            .map(|(_, &BoundingBox(bounds))| bounds)
+            .collect();
+        if let Some(tile_map) = world.try_fetch::<TileMap>() {
+            obstacles.extend(tile_map.solid_cells_overlapping(world_bounds).into_iter().map(|(_, _, bounds)| bounds));
+        }
+        Self::new(world_bounds, obstacles)
+    }
+
+    /// The area of the world covered by the grid
+    pub fn world_bounds(&self) -> Rect {
+        self.world_bounds
+    }
+
+    /// Whether an entity with the given bounds would touch an obstacle or leave the world
+    pub fn blocks(&self, bounds: Rect) -> bool {
+        let world = self.world_bounds;
+        let outside = bounds.left() < world.left() || bounds.right() > world.right()
+            || bounds.top() < world.top() || bounds.bottom() > world.bottom();
+        outside || self.cells_overlapping(bounds).any(|cell| {
+            self.cell_obstacles[self.index(cell)].iter().any(|&obstacle| self.obstacles[obstacle].has_intersection(bounds))
+        })
+    }
+
+    /// Whether the straight line between two points of the world crosses no obstacle
+    pub fn line_of_sight(&self, from: Point, to: Point) -> bool {
+        let area = Rect::new(
+            from.x().min(to.x()),
+            from.y().min(to.y()),
+            from.x().abs_diff(to.x()) + 1,
+            from.y().abs_diff(to.y()) + 1,
+        );
+        !self.cells_overlapping(area).any(|cell| {
+            self.cell_obstacles[self.index(cell)].iter().any(|&obstacle| crosses(self.obstacles[obstacle], from, to))
+        })
+    }
+
+    /// The cell containing the given point, or the closest one for points outside of the grid
+    fn cell_at(&self, point: Point) -> Cell {
+        let column = (point.x() - self.world_bounds.x()).div_euclid(PATH_CELL_SIZE as i32);
+        let row = (point.y() - self.world_bounds.y()).div_euclid(PATH_CELL_SIZE as i32);
+        (column.clamp(0, self.columns - 1), row.clamp(0, self.rows - 1))
+    }
+
+    /// The cells overlapping the given bounds, including the closest ones for bounds reaching
+    /// outside of the grid
+    fn cells_overlapping(&self, bounds: Rect) -> impl Iterator<Item = Cell> {
+        let (first_column, first_row) = self.cell_at(bounds.top_left());
+        let (last_column, last_row) = self.cell_at(bounds.bottom_right().offset(-1, -1));
+        (first_row..=last_row).flat_map(move |row| (first_column..=last_column).map(move |column| (column, row)))
+    }
+
+    fn contains(&self, (column, row): Cell) -> bool {
+        (0..self.columns).contains(&column) && (0..self.rows).contains(&row)
+    }
+
+    fn index(&self, (column, row): Cell) -> usize {
+        (row * self.columns + column) as usize
+    }
+
+    fn cell(&self, index: usize) -> Cell {
+        (index as i32 % self.columns, index as i32 / self.columns)
+    }
+
+    fn center(&self, (column, row): Cell) -> Point {
+        let half = PATH_CELL_SIZE as i32 / 2;
+        self.world_bounds.top_left().offset(column * PATH_CELL_SIZE as i32 + half, row * PATH_CELL_SIZE as i32 + half)
+    }
+}
+
+/// Whether the line segment between two points goes through the given bounds, found by clipping
+/// the segment to the bounds one edge after another
+fn crosses(bounds: Rect, from: Point, to: Point) -> bool {
+    let (x, y) = (from.x() as f64, from.y() as f64);
+    let (dx, dy) = ((to.x() - from.x()) as f64, (to.y() - from.y()) as f64);
+    // The fractions of the segment left inside the bounds
+    let (mut enter, mut exit) = (0.0f64, 1.0f64);
+    let edges = [
+        (-dx, x - bounds.left() as f64),
+        (dx, bounds.right() as f64 - x),
+        (-dy, y - bounds.top() as f64),
+        (dy, bounds.bottom() as f64 - y),
+    ];
+    for (towards_edge, distance_to_edge) in edges {
+        if towards_edge == 0.0 {
+            // Parallel to the edge, so either always inside of it or never
+            if distance_to_edge <= 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let fraction = distance_to_edge / towards_edge;
+        if towards_edge < 0.0 {
+            enter = enter.max(fraction);
+        } else {
+            exit = exit.min(fraction);
+        }
+        if enter >= exit {
+            return false;
+        }
+    }
+    true
+}
+
+/// How the `Pathfinder` searches its `NavGrid`. Both find paths of the same length.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PathSearch {
+    /// A* from cell to cell
+    #[default]
+    AStar,
+    /// A* from jump point to jump point, skipping over the cells in between. Faster across large
+    /// open areas.
+    JumpPoint,
+}
+
+/// The cells of a path found by a search, not including the cell it started from
+#[derive(Debug, Clone)]
+struct CellPath {
+    cells: Vec<Cell>,
+    /// Whether the path ends in the cell of the goal, rather than in the closest cell to it that
+    /// could be reached
+    reached_goal: bool,
+}
+
+/// Finds paths through a `NavGrid`. Inserted in the world by the `Game` once the world has been
+/// populated, as obstacles never move.
+///
+/// Searches are cached by start cell, goal cell and entity size, so entities going the same way
+/// share the work, and so does an entity asking again for the same path.
+#[derive(Debug)]
+pub struct Pathfinder {
+    grid: NavGrid,
+    search: PathSearch,
+    /// Which cells are walkable by entities of each size, worked out as searches reach them
+    walkable: HashMap<(u32, u32), Vec<Option<bool>>>,
+    cache: HashMap<(Cell, Cell, (u32, u32)), CellPath>,
+    recent_paths: VecDeque<Vec<Point>>,
+}
+
+impl Pathfinder {
+    /// A pathfinder through the given grid, searching it the given way
+    pub fn new(grid: NavGrid, search: PathSearch) -> Self {
+        Self {
+            grid,
+            search,
+            walkable: HashMap::new(),
+            cache: HashMap::new(),
+            recent_paths: VecDeque::new(),
+        }
+    }
+
+    /// The grid paths are searched through
+    pub fn grid(&self) -> &NavGrid {
+        &self.grid
+    }
+
+    /// How paths are searched
+    pub fn search(&self) -> PathSearch {
+        self.search
+    }
+
+    /// Replaces the grid paths are searched through, e.g. once obstacles were added, and forgets
+    /// every path found through the previous one
+    pub fn set_grid(&mut self, grid: NavGrid) {
+        self.grid = grid;
+        self.walkable.clear();
+        self.cache.clear();
+    }
+
+    /// The waypoints leading an entity of the given size from `start` to `goal` without running
+    /// into any obstacle, ending with `goal`. Straight stretches of the path are merged into a
+    /// single waypoint. When `goal` cannot be reached, the path leads to the reachable point
+    /// closest to it, which may leave the path empty.
+    pub fn find_path(&mut self, start: Point, goal: Point, size: (u32, u32)) -> Vec<Point> {
+        let start_cell = self.grid.cell_at(start);
+        let goal_cell = self.grid.cell_at(goal);
+        let key = (start_cell, goal_cell, size);
+        if !self.cache.contains_key(&key) {
+            if self.cache.len() >= PATH_CACHE_CAPACITY {
+                self.cache.clear();
+            }
+            let cell_count = self.grid.cell_obstacles.len();
+            let mut cells = WalkableCells {
+                grid: &self.grid,
+                size: (size.0 + 2 * CLEARANCE, size.1 + 2 * CLEARANCE),
+                start: start_cell,
+                walkable: self.walkable.entry(size).or_insert_with(|| vec![None; cell_count]),
+            };
+            let path = match self.search {
+                PathSearch::AStar => a_star(&mut cells, start_cell, goal_cell),
+                // Jump-point search only visits some of the reachable cells, so the closest one
+                // to an unreachable goal is left to A*
+                PathSearch::JumpPoint => jump_point_search(&mut cells, start_cell, goal_cell)
+                    .unwrap_or_else(|| a_star(&mut cells, start_cell, goal_cell)),
+            };
+            self.cache.insert(key, path);
+        }
+
+        let CellPath {cells, reached_goal} = &self.cache[&key];
+        let mut path: Vec<Point> = cells.iter().map(|&cell| self.grid.center(cell)).collect();
+        // The goal is only walked to exactly when it lies in the last cell, which is known to be clear
+        if *reached_goal && !self.grid.blocks(Rect::from_center(goal, size.0, size.1)) {
+            path.push(goal);
+        }
+        let path = merge_straight_lines(start, path);
+
+        if self.recent_paths.len() == RECENT_PATH_COUNT {
+            self.recent_paths.pop_front();
+        }
+        self.recent_paths.push_back(std::iter::once(start).chain(path.iter().copied()).collect());
+        path
+    }
+
+    /// The last paths found, from the oldest to the newest, each starting with the point it was
+    /// found from. Meant for debugging.
+    pub fn recent_paths(&self) -> impl Iterator<Item = &[Point]> {
+        self.recent_paths.iter().map(Vec::as_slice)
+    }
+}
+
+/// The cells of a grid walkable by an entity of a given size
+struct WalkableCells<'a> {
+    grid: &'a NavGrid,
+    /// The bounds of the entity, including the `CLEARANCE`
+    size: (u32, u32),
+    /// The cell the entity starts from, which it can always walk out of
+    start: Cell,
+    walkable: &'a mut Vec<Option<bool>>,
+}
+
+impl WalkableCells<'_> {
+    fn is_walkable(&mut self, cell: Cell) -> bool {
+        if !self.grid.contains(cell) {
+            return false;
+        }
+        if cell == self.start {
+            return true;
+        }
+        let (grid, (width, height)) = (self.grid, self.size);
+        *self.walkable[grid.index(cell)].get_or_insert_with(|| !grid.blocks(Rect::from_center(grid.center(cell), width, height)))
+    }
+}
+
+/// The Manhattan distance between two cells, in cells
+fn distance((column, row): Cell, (other_column, other_row): Cell) -> u32 {
+    column.abs_diff(other_column) + row.abs_diff(other_row)
+}
+
+/// A* over the cells of the grid. Ties between cells of equal cost are broken by the order in
+/// which they were found so that the same query always finds the same path.
+fn a_star(cells: &mut WalkableCells, start: Cell, goal: Cell) -> CellPath {
+    let grid = cells.grid;
+    let cell_count = grid.cell_obstacles.len();
+    let mut cost = vec![u32::MAX; cell_count];
+    let mut came_from = vec![usize::MAX; cell_count];
+    let mut open = BinaryHeap::new();
+    let start_index = grid.index(start);
+    cost[start_index] = 0;
+    open.push(Reverse((distance(start, goal), 0u32, start_index)));
+    let mut found = 0u32;
+    // The closest cell to the goal reached so far, in case the goal itself cannot be reached
+    let mut closest = (distance(start, goal), start_index);
+
+    while let Some(Reverse((_, _, index))) = open.pop() {
+        let cell = grid.cell(index);
+        if cell == goal {
+            closest = (0, index);
+            break;
+        }
+        for (dx, dy) in MOVES {
+            let neighbour = (cell.0 + dx, cell.1 + dy);
+            if !cells.is_walkable(neighbour) {
+                continue;
+            }
+            let neighbour_index = grid.index(neighbour);
+            let neighbour_cost = cost[index] + 1;
+            if neighbour_cost >= cost[neighbour_index] {
+                continue;
+            }
+            cost[neighbour_index] = neighbour_cost;
+            came_from[neighbour_index] = index;
+            let remaining = distance(neighbour, goal);
+            closest = closest.min((remaining, neighbour_index));
+            found += 1;
+            open.push(Reverse((neighbour_cost + remaining, found, neighbour_index)));
+        }
+    }
+
+    let (remaining, end_index) = closest;
+    CellPath {cells: trace_back(grid, &came_from, start_index, end_index), reached_goal: remaining == 0}
+}
+
+/// Jump-point search for grids where entities move in four directions. Of all the shortest paths
+/// between two cells, only the ones moving horizontally as early as possible are searched: a
+/// vertical move is only followed by a horizontal one when an obstacle prevented that horizontal
+/// move from happening earlier. The search can then jump over the cells where nothing else could
+/// happen. Returns `None` when the goal cannot be reached.
+fn jump_point_search(cells: &mut WalkableCells, start: Cell, goal: Cell) -> Option<CellPath> {
+    let grid = cells.grid;
+    let cell_count = grid.cell_obstacles.len();
+    let mut cost = vec![u32::MAX; cell_count];
+    let mut came_from = vec![usize::MAX; cell_count];
+    let mut open = BinaryHeap::new();
+    let start_index = grid.index(start);
+    cost[start_index] = 0;
+    open.push(Reverse((distance(start, goal), 0u32, start_index)));
+    let mut found = 0u32;
+
+    while let Some(Reverse((_, _, index))) = open.pop() {
+        let cell = grid.cell(index);
+        if cell == goal {
+            return Some(CellPath {cells: trace_back(grid, &came_from, start_index, index), reached_goal: true});
+        }
+        let arrival = match came_from[index] {
+            usize::MAX => None,
+            parent => {
+                let parent = grid.cell(parent);
+                Some(((cell.0 - parent.0).signum(), (cell.1 - parent.1).signum()))
+            },
+        };
+        for direction in jump_directions(cells, cell, arrival) {
+            let jump_point = match jump(cells, cell, direction, goal) {
+                Some(jump_point) => jump_point,
+                None => continue,
+            };
+            let jump_index = grid.index(jump_point);
+            let jump_cost = cost[index] + distance(cell, jump_point);
+            if jump_cost >= cost[jump_index] {
+                continue;
+            }
+            cost[jump_index] = jump_cost;
+            came_from[jump_index] = index;
+            found += 1;
+            open.push(Reverse((jump_cost + distance(jump_point, goal), found, jump_index)));
+        }
+    }
+    None
+}
+
+/// The directions worth searching from a jump point reached with the given move, or from the
+/// start of the search
+fn jump_directions(cells: &mut WalkableCells, (column, row): Cell, arrival: Option<Cell>) -> Vec<Cell> {
+    match arrival {
+        None => MOVES.to_vec(),
+        Some((dx, 0)) => vec![(dx, 0), (0, -1), (0, 1)],
+        Some((_, dy)) => {
+            let mut directions = vec![(0, dy)];
+            directions.extend([-1, 1].into_iter()
+                .filter(|&dx| is_forced(cells, (column, row), dx, dy))
+                .map(|dx| (dx, 0)));
+            directions
+        },
+    }
+}
+
+/// Whether a path moving vertically by `dy` into the given cell must turn horizontally by `dx`
+/// right there, because the cell next to the one it came from is not walkable
+fn is_forced(cells: &mut WalkableCells, (column, row): Cell, dx: i32, dy: i32) -> bool {
+    !cells.is_walkable((column + dx, row - dy)) && cells.is_walkable((column + dx, row))
+}
+
+/// The next jump point found by moving from `cell` in the given direction, if any. Moving
+/// vertically stops at the goal and wherever the path is forced to turn. Moving horizontally also
+/// stops wherever moving vertically from there would find a jump point.
+fn jump(cells: &mut WalkableCells, mut cell: Cell, (dx, dy): Cell, goal: Cell) -> Option<Cell> {
+    loop {
+        cell = (cell.0 + dx, cell.1 + dy);
+        if !cells.is_walkable(cell) {
+            return None;
+        }
+        if cell == goal {
+            return Some(cell);
+        }
+        let is_jump_point = if dy != 0 {
+            is_forced(cells, cell, -1, dy) || is_forced(cells, cell, 1, dy)
+        } else {
+            jump(cells, cell, (0, -1), goal).is_some() || jump(cells, cell, (0, 1), goal).is_some()
+        };
+        if is_jump_point {
+            return Some(cell);
+        }
+    }
+}
+
+/// The cells from the cell after `start_index` to `end_index`, following `came_from`. Cells
+/// found from further away than their neighbours, like jump points, are joined by straight lines.
+fn trace_back(grid: &NavGrid, came_from: &[usize], start_index: usize, end_index: usize) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    let mut index = end_index;
+    while index != start_index {
+        let (cell, previous) = (grid.cell(index), grid.cell(came_from[index]));
+        let direction = ((cell.0 - previous.0).signum(), (cell.1 - previous.1).signum());
+        let mut step = cell;
+        while step != previous {
+            cells.push(step);
+            step = (step.0 - direction.0, step.1 - direction.1);
+        }
+        index = came_from[index];
+    }
+    cells.reverse();
+    cells
+}
+
+/// Removes the waypoints in the middle of straight lines, keeping only the turns and the end
+fn merge_straight_lines(start: Point, path: Vec<Point>) -> Vec<Point> {
+    let mut merged: Vec<Point> = Vec::with_capacity(path.len());
+    for point in path {
+        let before = match merged.len() {
+            0 => None,
+            1 => Some(start),
+            len => Some(merged[len - 2]),
+        };
+        if let (Some(before), Some(&last)) = (before, merged.last()) {
+            let same_column = before.x() == last.x() && last.x() == point.x();
+            let same_row = before.y() == last.y() && last.y() == point.y();
+            if same_column || same_row {
+                merged.pop();
+            }
+        }
+        merged.push(point);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+
+    /// The width and height of the test worlds, in cells
+    const CELLS: u32 = 20;
+
+    /// A world of `CELLS` by `CELLS` cells
+    fn world() -> Rect {
+        Rect::new(0, 0, CELLS * PATH_CELL_SIZE, CELLS * PATH_CELL_SIZE)
+    }
+
+    /// The center of the given cell of a grid covering the `world`
+    fn center(column: i32, row: i32) -> Point {
+        NavGrid::new(world(), Vec::new()).center((column, row))
+    }
+
+    #[test]
+    fn jump_point_search_finds_paths_as_short_as_a_star() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        for _ in 0..50 {
+            // Roughly a quarter of the cells are blocked
+            let obstacles: Vec<_> = (0..100)
+                .map(|_| Rect::new(rng.gen_range(0..CELLS as i32) * PATH_CELL_SIZE as i32, rng.gen_range(0..CELLS as i32) * PATH_CELL_SIZE as i32, PATH_CELL_SIZE, PATH_CELL_SIZE))
+                .collect();
+            let grid = NavGrid::new(world(), obstacles);
+            let mut walkable = vec![None; grid.cell_obstacles.len()];
+            for _ in 0..20 {
+                let start = (rng.gen_range(0..CELLS as i32), rng.gen_range(0..CELLS as i32));
+                let goal = (rng.gen_range(0..CELLS as i32), rng.gen_range(0..CELLS as i32));
+                walkable.iter_mut().for_each(|cell| *cell = None);
+                let mut cells = WalkableCells {grid: &grid, size: (1, 1), start, walkable: &mut walkable};
+                let a_star_path = a_star(&mut cells, start, goal);
+                match jump_point_search(&mut cells, start, goal) {
+                    Some(jump_point_path) => {
+                        assert!(a_star_path.reached_goal, "{:?} to {:?}", start, goal);
+                        assert_eq!(jump_point_path.cells.len(), a_star_path.cells.len(), "{:?} to {:?}", start, goal);
+                    },
+                    None => assert!(!a_star_path.reached_goal, "{:?} to {:?}", start, goal),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn searches_are_cached_by_cell() {
+        let mut pathfinder = Pathfinder::new(NavGrid::new(world(), Vec::new()), PathSearch::AStar);
+        let path = pathfinder.find_path(center(1, 1), center(10, 1), (5, 5));
+        assert_eq!(path, vec![center(10, 1)]);
+        // Another point of the same start cell, going to the same goal cell, follows the same
+        // cells once it has stepped to the row of their centers
+        let path = pathfinder.find_path(center(1, 1).offset(2, 2), center(10, 1), (5, 5));
+        assert_eq!(path, vec![center(2, 1), center(10, 1)]);
+        assert_eq!(pathfinder.cache.len(), 1);
+
+        // A bigger entity needs its own search
+        pathfinder.find_path(center(1, 1), center(10, 1), (8, 8));
+        assert_eq!(pathfinder.cache.len(), 2);
+    }
+
+    #[test]
+    fn changing_the_grid_forgets_the_cached_searches() {
+        let mut pathfinder = Pathfinder::new(NavGrid::new(world(), Vec::new()), PathSearch::JumpPoint);
+        assert_eq!(pathfinder.find_path(center(1, 5), center(10, 5), (5, 5)), vec![center(10, 5)]);
+
+        // A wall across the way, open at the bottom
+        let wall = Rect::new(5 * PATH_CELL_SIZE as i32, 0, PATH_CELL_SIZE, 15 * PATH_CELL_SIZE);
+        pathfinder.set_grid(NavGrid::new(world(), [wall]));
+        assert_eq!(pathfinder.cache.len(), 0);
+        let path = pathfinder.find_path(center(1, 5), center(10, 5), (5, 5));
+        assert_eq!(path.last(), Some(&center(10, 5)));
+        assert!(path.iter().any(|point| point.y() > wall.bottom()), "{:?}", path);
+    }
+
+    #[test]
+    fn the_cache_is_cleared_once_full() {
+        let mut pathfinder = Pathfinder::new(NavGrid::new(world(), Vec::new()), PathSearch::AStar);
+        let goals = (0..CELLS as i32).flat_map(|row| (0..CELLS as i32).map(move |column| center(column, row)));
+        for goal in goals.clone().take(PATH_CACHE_CAPACITY) {
+            pathfinder.find_path(center(0, 0), goal, (5, 5));
+        }
+        assert_eq!(pathfinder.cache.len(), PATH_CACHE_CAPACITY);
+        // Asking again for a cached path does not clear the cache
+        pathfinder.find_path(center(0, 0), center(3, 0), (5, 5));
+        assert_eq!(pathfinder.cache.len(), PATH_CACHE_CAPACITY);
+
+        let goal = goals.clone().nth(PATH_CACHE_CAPACITY).unwrap();
+        assert_eq!(pathfinder.find_path(center(0, 0), goal, (5, 5)).last(), Some(&goal));
+        assert_eq!(pathfinder.cache.len(), 1);
+    }
+}
@@ -2,44 +2,187 @@
 //! thread. It cannot be executed in parallel like other systems. Another complication is that it
 //! returns a `Result` whereas normal systems do not return anything.
 
-use specs::{SystemData, ReadStorage, Join, World, prelude::ResourceId};
+use specs::{SystemData, Entities, Read, ReadExpect, ReadStorage, Join, World, prelude::ResourceId};
 use sdl2::{
+    pixels::Color,
     rect::{Point, Rect},
-    render::{WindowCanvas, Texture},
+    render::WindowCanvas,
 };
 
-use crate::components::{BoundingBox, Sprite, Player};
+use crate::assets::LoadedTextures;
+use crate::camera::Camera;
+use crate::tilemap::TileMap;
+use crate::pathfinding::Pathfinder;
+use crate::resources::InterpolationAlpha;
+use crate::vector::Vector;
+use crate::components::{BoundingBox, Position, PreviousPosition, Sprite, RenderLayer};
+
+/// The color of the paths drawn by `PathOverlayData`
+const PATH_COLOR: Color = Color::RGB(255, 64, 64);
+/// The width and height of the squares marking the waypoints of the paths, in pixels
+const WAYPOINT_SIZE: u32 = 6;
 
 /// Data from the world required by the renderer
 #[derive(SystemData)]
 pub struct RendererData<'a> {
-    players: ReadStorage<'a, Player>,
+    entities: Entities<'a>,
     bounding_boxes: ReadStorage<'a, BoundingBox>,
+    positions: ReadStorage<'a, Position>,
+    previous_positions: ReadStorage<'a, PreviousPosition>,
     sprites: ReadStorage<'a, Sprite>,
+    render_layers: ReadStorage<'a, RenderLayer>,
+    interpolation_alpha: Read<'a, InterpolationAlpha>,
+    camera: Read<'a, Camera>,
+    tile_map: Read<'a, TileMap>,
 }
 
 impl<'a> RendererData<'a> {
-    pub fn render(&self, canvas: &mut WindowCanvas, textures: &Vec<Texture>) -> Result<(), String> {
+    /// Draws the `TileMap` and then the sprite of every entity on top of it, sorted by
+    /// `RenderLayer` and depth, as seen through the `Camera`
+    pub fn render(&self, canvas: &mut WindowCanvas, textures: &LoadedTextures) -> Result<(), String> {
         let RendererData {
-            players,
-            bounding_boxes, 
-            sprites
+            entities,
+            bounding_boxes,
+            positions,
+            previous_positions,
+            sprites,
+            render_layers,
+            interpolation_alpha,
+            camera,
+            tile_map,
         } = self;
+        let InterpolationAlpha(alpha) = **interpolation_alpha;
+
+        render_tile_map(canvas, textures, tile_map, camera, alpha)?;
 
-        // The screen coordinate system has (0, 0) in its top-left corner whereas the
-        // world coordinate system is centered on the player.
-        let (width, height) = canvas.output_size()?;
-        let world_to_screen_offset = Point::new(width as i32 / 2, height as i32 / 2);
-        let mut player_bounds = Rect::from_center((0,0), 0, 0);
-        for (_, BoundingBox(temp_player_bounds)) in (players, bounding_boxes).join() {
-            player_bounds = temp_player_bounds.clone(); //TODO: find a clean code way to retrieve this from storage
+        let mut draw_calls = Vec::new();
+        for (entity, bounds, position, previous_position, &Sprite {texture, region: sprite_rect}, layer) in (entities, bounding_boxes, positions.maybe(), previous_positions.maybe(), sprites, render_layers.maybe()).join() {
+            let center = interpolated_center(bounds, position, previous_position, alpha);
+            // The bottom of the bounding box is where the entity touches the ground
+            let depth = center.y.round() as i32 + bounds.0.height() as i32 / 2;
+            let screen_rect = camera.world_area_to_screen(center, sprite_rect.width(), sprite_rect.height(), alpha);
+            let layer = layer.copied().unwrap_or_default();
+            // The entity id breaks ties so that overlapping sprites at the same depth don't flicker
+            draw_calls.push(((layer, depth, entity.id()), texture, sprite_rect, screen_rect));
         }
-        for (&BoundingBox(bounds), &Sprite {texture_id, region: sprite_rect}) in (bounding_boxes, sprites).join() {
-            let screen_pos = bounds.center() - player_bounds.center() + world_to_screen_offset;
-            let screen_rect = Rect::from_center(screen_pos, sprite_rect.width(), sprite_rect.height());
-            canvas.copy(&textures[texture_id], sprite_rect, screen_rect)?;
+        draw_calls.sort_unstable_by_key(|&(order, ..)| order);
+
+        for (_, texture, sprite_rect, screen_rect) in draw_calls {
+            // Textures registered during this frame are loaded before the next one
+            if let Some(texture) = textures.get(texture) {
+                canvas.copy(texture, sprite_rect, screen_rect)?;
+            }
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Data from the world required to draw the paths recently found by the `Pathfinder` over the
+/// world, for debugging
+#[derive(SystemData)]
+pub struct PathOverlayData<'a> {
+    pathfinder: ReadExpect<'a, Pathfinder>,
+    interpolation_alpha: Read<'a, InterpolationAlpha>,
+    camera: Read<'a, Camera>,
+}
+
+impl<'a> PathOverlayData<'a> {
+    /// Draws each path as a line from the point it was found from through each of its
+    /// waypoints, which are marked with a square
+    pub fn render(&self, canvas: &mut WindowCanvas) -> Result<(), String> {
+        let InterpolationAlpha(alpha) = *self.interpolation_alpha;
+        canvas.set_draw_color(PATH_COLOR);
+        for path in self.pathfinder.recent_paths() {
+            let points: Vec<Point> = path.iter().map(|&point| self.camera.world_to_screen(point, alpha)).collect();
+            canvas.draw_lines(points.as_slice())?;
+            for &point in &points[1..] {
+                canvas.fill_rect(Rect::from_center(point, WAYPOINT_SIZE, WAYPOINT_SIZE))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draws the tiles of every layer of the map that are visible through the camera
+fn render_tile_map(canvas: &mut WindowCanvas, textures: &LoadedTextures, tile_map: &TileMap, camera: &Camera, alpha: f64) -> Result<(), String> {
+    let (viewport_width, viewport_height) = camera.viewport;
+    let top_left = camera.screen_to_world(Point::new(0, 0), alpha);
+    let bottom_right = camera.screen_to_world(Point::new(viewport_width as i32, viewport_height as i32), alpha);
+    // One extra tile on each side covers the rounding of the conversion
+    let (tile_width, tile_height) = (tile_map.tile_size.0 as i32, tile_map.tile_size.1 as i32);
+    let visible_area = Rect::new(
+        top_left.x() - tile_width,
+        top_left.y() - tile_height,
+        (bottom_right.x() - top_left.x() + 2 * tile_width).max(1) as u32,
+        (bottom_right.y() - top_left.y() + 2 * tile_height).max(1) as u32,
+    );
+    let ((first_column, first_row), (last_column, last_row)) = match tile_map.cells_overlapping(visible_area) {
+        Some(cells) => cells,
+        None => return Ok(()),
+    };
+
+    let texture = match tile_map.tileset.texture.and_then(|texture| textures.get(texture)) {
+        Some(texture) => texture,
+        None => return Ok(()),
+    };
+    for layer in &tile_map.layers {
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                let tile = match layer[(row * tile_map.columns + column) as usize] {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                // Converting both corners, rather than the center and the size, keeps neighbouring
+                // tiles from leaving gaps between them when zoomed
+                let bounds = tile_map.cell_bounds(column, row);
+                let top_left = camera.world_to_screen(bounds.top_left(), alpha);
+                let bottom_right = camera.world_to_screen(bounds.bottom_right(), alpha);
+                let screen_rect = Rect::new(
+                    top_left.x(),
+                    top_left.y(),
+                    (bottom_right.x() - top_left.x()).max(0) as u32,
+                    (bottom_right.y() - top_left.y()).max(0) as u32,
+                );
+                canvas.copy(texture, tile_map.tile_region(tile), screen_rect)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The center of an entity somewhere between its previous and current position. The exact
+/// `Position` is interpolated so that slow entities glide across the screen instead of stepping
+/// from one whole pixel to the next. Entities that have never moved are drawn at their current
+/// position, and entities without a `Position` at the center of their bounding box.
+fn interpolated_center(
+    bounds: &BoundingBox,
+    position: Option<&Position>,
+    previous_position: Option<&PreviousPosition>,
+    alpha: f64,
+) -> Vector {
+    match (position, previous_position) {
+        (Some(&Position(current)), Some(&PreviousPosition(previous))) => {
+            previous + (current - previous) * alpha as f32
+        },
+        (Some(&Position(current)), None) => current,
+        (None, _) => Vector::from_point(bounds.0.center()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolation_keeps_fractions_of_a_pixel() {
+        let bounds = BoundingBox(Rect::from_center(Point::new(10, 0), 4, 4));
+        let position = Position(Vector::new(10.4, 0.0));
+        let previous_position = PreviousPosition(Vector::new(10.0, 0.0));
+        // Rounding the bounding boxes would have drawn the entity at x = 10 for the whole update
+        let center = interpolated_center(&bounds, Some(&position), Some(&previous_position), 0.5);
+        assert!((center.x - 10.2).abs() < 1e-5, "{:?}", center);
+        assert_eq!(interpolated_center(&bounds, Some(&position), None, 0.5), Vector::new(10.4, 0.0));
+        assert_eq!(interpolated_center(&bounds, None, None, 0.5), Vector::new(10.0, 0.0));
+    }
+}
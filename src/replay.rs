@@ -0,0 +1,213 @@
+//! Recording play sessions and replaying them. A recording holds everything needed to reproduce a
+//! game exactly: the level, the seed of the `GameRng`, the keyboard input delivered at every
+//! tick and the changes of the `TimeScale`. Replaying feeds that input back to the world instead
+//! of the events of the `EventPump`.
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::game::Game;
+use crate::headless::InputScript;
+use crate::resources::{KeyboardEvent, GameStatus, TimeScale};
+
+/// A recorded play session, stored as a RON file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// The path of the level file that was played
+    pub level: PathBuf,
+    /// The seed of the random number generator of the world
+    pub seed: u64,
+    /// The number of ticks the session lasted
+    pub ticks: u64,
+    /// The keyboard input delivered to the world, by tick
+    pub inputs: InputScript,
+    /// The time scale of the game from each tick it changed at on, in order
+    #[serde(default)]
+    pub time_scales: Vec<(u64, f64)>,
+}
+
+/// The reasons a recording can fail to be saved or loaded
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The recording file could not be read or written
+    Io {
+        /// The path of the recording file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The recording file is not a valid recording
+    Parse {
+        /// The path of the recording file
+        path: PathBuf,
+        /// The underlying error, including the position of the error in the file
+        source: ron::error::SpannedError,
+    },
+    /// The recording file is well-formed but describes a session that cannot be replayed
+    Invalid {
+        /// The path of the recording file
+        path: PathBuf,
+        /// What is wrong with the recording
+        message: String,
+    },
+    /// The recording could not be serialized
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io {path, source} => write!(f, "Could not access recording {}: {}", path.display(), source),
+            ReplayError::Parse {path, source} => write!(f, "Could not parse recording {}: {}", path.display(), source),
+            ReplayError::Invalid {path, message} => write!(f, "Invalid recording {}: {}", path.display(), message),
+            ReplayError::Serialize(source) => write!(f, "Could not serialize recording: {}", source),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Io {source, ..} => Some(source),
+            ReplayError::Parse {source, ..} => Some(source),
+            ReplayError::Invalid {..} => None,
+            ReplayError::Serialize(source) => Some(source),
+        }
+    }
+}
+
+impl Recording {
+    /// Starts recording a session of the given level, played with the given seed
+    pub fn new(level: impl Into<PathBuf>, seed: u64) -> Self {
+        Self {
+            level: level.into(),
+            seed,
+            ticks: 0,
+            inputs: InputScript::default(),
+            time_scales: Vec::new(),
+        }
+    }
+
+    /// Records the input delivered to the world at the given tick. Must be called for every tick,
+    /// in order, even when there is no input, so that the length of the session is known.
+    pub fn record(&mut self, tick: u64, keyboard_event: Option<KeyboardEvent>) {
+        if let Some(keyboard_event) = keyboard_event {
+            self.inputs.push(tick, keyboard_event);
+        }
+        self.ticks = self.ticks.max(tick + 1);
+    }
+
+    /// Records the time scale of the game at the given tick, if it changed since the last call
+    pub fn record_time_scale(&mut self, tick: u64, time_scale: f64) {
+        // The game starts at the default time scale
+        let current = self.time_scales.last().map_or(1.0, |&(_, time_scale)| time_scale);
+        if time_scale != current {
+            self.time_scales.push((tick, time_scale));
+        }
+    }
+
+    /// The time scale the game changed to at the given tick, if it changed
+    pub fn time_scale_at(&self, tick: u64) -> Option<f64> {
+        self.time_scales.iter().rev()
+            .find(|&&(change_tick, _)| change_tick == tick)
+            .map(|&(_, time_scale)| time_scale)
+    }
+
+    /// The input to deliver to the world at the given tick
+    pub fn event_at(&self, tick: u64) -> Option<KeyboardEvent> {
+        self.inputs.event_at(tick)
+    }
+
+    /// Whether every recorded tick has been replayed once the game reaches the given tick
+    pub fn is_finished(&self, tick: u64) -> bool {
+        tick >= self.ticks
+    }
+
+    /// Replays the whole session on a game built from the same level and seed and returns the
+    /// final status of the game
+    pub fn replay(&self, game: &mut Game) -> GameStatus {
+        let mut status = game.status();
+        while status == GameStatus::Running && !self.is_finished(game.tick()) {
+            if let Some(time_scale) = self.time_scale_at(game.tick()) {
+                game.set_time_scale(time_scale);
+            }
+            status = game.step(self.event_at(game.tick()));
+        }
+        status
+    }
+
+    /// Writes the recording to the given path
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let path = path.as_ref();
+        let source = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(ReplayError::Serialize)?;
+        fs::write(path, source)
+            .map_err(|source| ReplayError::Io {path: path.to_path_buf(), source})
+    }
+
+    /// Reads and validates the recording at the given path
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|source| ReplayError::Io {path: path.to_path_buf(), source})?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses and validates a recording. The `path` is only used in error messages.
+    pub fn parse(source: &str, path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let path = path.as_ref();
+        let recording: Recording = ron::from_str(source)
+            .map_err(|source| ReplayError::Parse {path: path.to_path_buf(), source})?;
+        recording.validate()
+            .map_err(|message| ReplayError::Invalid {path: path.to_path_buf(), message})?;
+        Ok(recording)
+    }
+
+    /// Checks everything that the file format itself cannot express
+    fn validate(&self) -> Result<(), String> {
+        if let Some((tick, time_scale)) = self.time_scales.iter().find(|&&(_, time_scale)| !TimeScale::is_valid(time_scale)) {
+            return Err(format!("time scales must be finite, at least 0 and small enough to step the game with, got {} at tick {}", time_scale, tick));
+        }
+        if let Some((tick, event)) = self.inputs.events().iter().find(|(_, event)| !event.is_finite()) {
+            return Err(format!("inputs must be finite, got {:?} at tick {}", event, tick));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording_with_time_scale(time_scale: &str) -> String {
+        format!(r#"(level: "level.ron", seed: 7, ticks: 10, inputs: [], time_scales: [(0, 0.0), (4, {})])"#, time_scale)
+    }
+
+    #[test]
+    fn parse_accepts_paused_and_scaled_time() {
+        let recording = Recording::parse(&recording_with_time_scale("2.5"), "test.ron").unwrap();
+        assert_eq!(recording.time_scale_at(0), Some(0.0));
+        assert_eq!(recording.time_scale_at(4), Some(2.5));
+    }
+
+    #[test]
+    fn parse_rejects_negative_and_non_finite_time_scales() {
+        for time_scale in ["-1.0", "inf", "NaN", "1e300"] {
+            let error = Recording::parse(&recording_with_time_scale(time_scale), "test.ron").unwrap_err();
+            assert!(matches!(error, ReplayError::Invalid {..}), "{} gave {}", time_scale, error);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_non_finite_headings() {
+        for heading in ["x: NaN, y: 0.0", "x: 1.0, y: -inf"] {
+            let source = format!(r#"(level: "level.ron", seed: 7, ticks: 10, inputs: [(0, Stop), (3, Move({}))])"#, heading);
+            let error = Recording::parse(&source, "test.ron").unwrap_err();
+            assert!(matches!(error, ReplayError::Invalid {..}), "{} gave {}", heading, error);
+        }
+    }
+}
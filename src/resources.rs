@@ -1,25 +1,173 @@
 use std::time::Duration;
 
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Serialize, Deserialize};
+use specs::Entity;
+
 use crate::direction::Direction;
 
 /// The amount of time elapsed since the last frame
 #[derive(Debug, Default)]
 pub struct TimeDelta(pub Duration);
 
-#[derive(Debug, PartialEq)]
+/// The amount of game time simulated since the game started, i.e. the sum of every `TimeDelta` so
+/// far. Timers of the world are measured against it rather than the wall clock, so that they
+/// follow pauses and replays of the game.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GameTime(pub Duration);
+
+/// How fast game time passes compared to real time: `0.5` is slow motion and `2.0` fast forward.
+/// Scales the `TimeDelta` of every update.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeScale(pub f64);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
+
+impl TimeScale {
+    /// Whether the given time scale can be used: finite, at least `0.0`, and small enough for a
+    /// scaled second to fit in a `Duration`, and therefore a scaled update too
+    pub fn is_valid(time_scale: f64) -> bool {
+        time_scale.is_finite() && time_scale >= 0.0 && Duration::try_from_secs_f64(time_scale).is_ok()
+    }
+}
+
+/// How far the rendered frame is between the previous and the current update of the world, from
+/// `0.0` (previous state) to `1.0` (current state)
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InterpolationAlpha(pub f64);
+
+/// The keyboard input delivered to the world for a single update
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum KeyboardEvent {
     /// Begin to move in the given direction
     MoveInDirection(Direction),
+    /// Begin to move with the given heading, see `Locomotion::heading`. Used for diagonals and
+    /// analog sticks.
+    Move {
+        /// The horizontal component of the heading
+        x: f32,
+        /// The vertical component of the heading
+        y: f32,
+    },
     /// Stop moving in the current direction
     Stop,
-    /// Escape event pressed
-    Escape,
+    /// Walk to the given point of the world around obstacles, e.g. after clicking there
+    MoveTo {
+        /// The horizontal position of the destination, in world coordinates
+        x: i32,
+        /// The vertical position of the destination, in world coordinates
+        y: i32,
+    },
+}
+
+impl KeyboardEvent {
+    /// Whether the numbers of the event are finite, which is required for the world to stay
+    /// finite too
+    pub fn is_finite(self) -> bool {
+        match self {
+            KeyboardEvent::Move {x, y} => x.is_finite() && y.is_finite(),
+            KeyboardEvent::MoveInDirection(_) | KeyboardEvent::Stop | KeyboardEvent::MoveTo {..} => true,
+        }
+    }
+}
+
+/// The only source of randomness of the world, shared by spawning and every system. The same seed
+/// and the same input always produce the same game. ChaCha8 is used rather than `StdRng`, whose
+/// algorithm may change between versions of `rand` and would break recorded games.
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl GameRng {
+    /// A random number generator starting from the given seed
+    pub fn from_seed(seed: u64) -> Self {
+        Self {seed, rng: ChaCha8Rng::seed_from_u64(seed)}
+    }
+
+    /// The seed this random number generator was created with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
 }
 
 /// The current status of the game
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GameStatus {
+    /// The game is still being played
     Running,
+    /// The player reached the goal
     Win,
+    /// The player was caught by an enemy
     Lose,
-}
\ No newline at end of file
+}
+
+/// What an entity ran into while moving
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Obstacle {
+    /// The edge of the world
+    WorldEdge,
+    /// A `Solid` entity
+    Entity(Entity),
+    /// A solid tile of the `TileMap`
+    Tile {
+        /// The column of the tile in the map
+        column: u32,
+        /// The row of the tile in the map
+        row: u32,
+    },
+}
+
+/// An entity that was stopped while moving during the last update of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionEvent {
+    /// The entity that was moving
+    pub entity: Entity,
+    /// What the entity ran into
+    pub obstacle: Obstacle,
+    /// The direction of the movement that was stopped
+    pub direction: Direction,
+}
+
+/// The collisions that happened during the last update of the world, written by the `Movement`
+/// system
+#[derive(Debug, Default)]
+pub struct CollisionEvents(pub Vec<CollisionEvent>);
+
+/// A named event of an animation frame, sent when the frame starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationEvent {
+    /// The animated entity
+    pub entity: Entity,
+    /// The name of the event, as given in the sprite sheet descriptor
+    pub name: String,
+}
+
+/// The animation events that happened during the last update of the world, written by the
+/// `Animator` system
+#[derive(Debug, Default)]
+pub struct AnimationEvents(pub Vec<AnimationEvent>);
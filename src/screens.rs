@@ -0,0 +1,99 @@
+//! The screens drawn on top of the world for each `GameState` other than `GameState::Playing`.
+//! Like the renderer, they draw with SDL and must be used on the main thread.
+
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::{BlendMode, WindowCanvas},
+};
+
+use crate::hud::{Hud, Anchor, TextStyle};
+use crate::states::GameState;
+
+/// The color of the symbols drawn in the middle of the screens
+const SYMBOL_COLOR: Color = Color::RGB(240, 240, 240);
+/// The width and height of the symbols drawn in the middle of the screens, in pixels
+const SYMBOL_SIZE: i32 = 120;
+/// The size of the font pixels of the text of the screens, see `TextStyle::scale`
+const TEXT_SCALE: u32 = 4;
+
+/// Draws the screen of the given state over whatever has already been drawn on the canvas: the
+/// world is tinted, a symbol is drawn above the middle of the canvas and the given lines of text
+/// are centered below it
+pub fn render_screen(canvas: &mut WindowCanvas, state: GameState, lines: &[String]) -> Result<(), String> {
+    let tint = match state {
+        GameState::Playing => return Ok(()),
+        GameState::Title => Color::RGBA(0, 0, 0, 192),
+        GameState::Paused => Color::RGBA(0, 0, 0, 128),
+        GameState::GameOver => Color::RGBA(128, 0, 0, 160),
+        GameState::Victory => Color::RGBA(255, 200, 0, 128),
+    };
+    let (width, height) = canvas.output_size()?;
+    let center = Point::new(width as i32 / 2, height as i32 / 2 - SYMBOL_SIZE);
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(tint);
+    canvas.fill_rect(Rect::new(0, 0, width, height))?;
+    canvas.set_blend_mode(BlendMode::None);
+
+    canvas.set_draw_color(SYMBOL_COLOR);
+    match state {
+        GameState::Playing => {},
+        GameState::Title => render_play_symbol(canvas, center)?,
+        GameState::Paused => render_pause_symbol(canvas, center)?,
+        GameState::GameOver => render_cross_symbol(canvas, center)?,
+        GameState::Victory => render_check_symbol(canvas, center)?,
+    }
+
+    let mut hud = Hud::new(0);
+    for line in lines {
+        hud.text(Anchor::Center, line.as_str(), TextStyle {scale: TEXT_SCALE, ..TextStyle::default()});
+    }
+    hud.render(canvas)
+}
+
+/// A triangle pointing right, filled one vertical line at a time
+fn render_play_symbol(canvas: &mut WindowCanvas, center: Point) -> Result<(), String> {
+    let left = center.x() - SYMBOL_SIZE / 2;
+    for x in 0..SYMBOL_SIZE {
+        let half_height = (SYMBOL_SIZE - x) / 2;
+        canvas.draw_line(
+            Point::new(left + x, center.y() - half_height),
+            Point::new(left + x, center.y() + half_height),
+        )?;
+    }
+    Ok(())
+}
+
+/// Two vertical bars
+fn render_pause_symbol(canvas: &mut WindowCanvas, center: Point) -> Result<(), String> {
+    let bar_width = SYMBOL_SIZE as u32 / 3;
+    let top = center.y() - SYMBOL_SIZE / 2;
+    canvas.fill_rect(Rect::new(center.x() - SYMBOL_SIZE / 2, top, bar_width, SYMBOL_SIZE as u32))?;
+    canvas.fill_rect(Rect::new(center.x() + SYMBOL_SIZE / 2 - bar_width as i32, top, bar_width, SYMBOL_SIZE as u32))?;
+    Ok(())
+}
+
+/// An X made of two thick diagonal lines
+fn render_cross_symbol(canvas: &mut WindowCanvas, center: Point) -> Result<(), String> {
+    let half = SYMBOL_SIZE / 2;
+    render_thick_line(canvas, center.offset(-half, -half), center.offset(half, half))?;
+    render_thick_line(canvas, center.offset(-half, half), center.offset(half, -half))
+}
+
+/// A check mark made of two thick lines
+fn render_check_symbol(canvas: &mut WindowCanvas, center: Point) -> Result<(), String> {
+    let half = SYMBOL_SIZE / 2;
+    let bottom = center.offset(-half / 3, half * 2 / 3);
+    render_thick_line(canvas, center.offset(-half, 0), bottom)?;
+    render_thick_line(canvas, bottom, center.offset(half, -half * 2 / 3))
+}
+
+/// A line about a tenth of `SYMBOL_SIZE` thick, drawn as several parallel lines
+fn render_thick_line(canvas: &mut WindowCanvas, start: Point, end: Point) -> Result<(), String> {
+    let thickness = SYMBOL_SIZE / 10;
+    for offset in -thickness / 2..=thickness / 2 {
+        canvas.draw_line(start.offset(offset, 0), end.offset(offset, 0))?;
+    }
+    Ok(())
+}
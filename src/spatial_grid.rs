@@ -0,0 +1,197 @@
+//! A uniform grid indexing the bounding boxes of entities, so that systems can find the entities
+//! near a given area without looking at every entity of the world.
+
+use std::collections::HashMap;
+
+use sdl2::rect::{Point, Rect};
+use specs::{BitSet, Component, Entity, ReadStorage};
+
+/// The default width and height of the cells of a `SpatialGrid`, in world coordinates
+pub const DEFAULT_CELL_SIZE: u32 = 128;
+
+/// Buckets entities by the grid cells their bounding box overlaps. Rebuilt from every
+/// `BoundingBox` at each update of the world by the `SpatialIndexer` system.
+#[derive(Debug)]
+pub struct SpatialGrid {
+    cell_size: i32,
+    cells: HashMap<(i32, i32), Vec<(Entity, Rect)>>,
+    /// The first and last cells holding entities since the last `clear`, if any
+    occupied: Option<((i32, i32), (i32, i32))>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    /// An empty grid made of square cells of the given size
+    pub fn new(cell_size: u32) -> Self {
+        assert!(cell_size > 0, "the cells of a spatial grid cannot be empty");
+        Self {
+            cell_size: cell_size as i32,
+            cells: HashMap::new(),
+            occupied: None,
+        }
+    }
+
+    /// Removes every entity from the grid. The memory of the cells that held entities is kept to
+    /// be reused, while the cells left empty since the previous clear are dropped, so that the grid
+    /// does not keep every cell the entities ever went through.
+    pub fn clear(&mut self) {
+        self.cells.retain(|_, cell| !cell.is_empty());
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+        self.occupied = None;
+    }
+
+    /// Adds an entity with the given bounds to every cell its bounds overlap
+    pub fn insert(&mut self, entity: Entity, bounds: Rect) {
+        let ((min_x, min_y), (max_x, max_y)) = self.cell_range(bounds);
+        for cell_y in min_y..=max_y {
+            for cell_x in min_x..=max_x {
+                self.cells.entry((cell_x, cell_y)).or_default().push((entity, bounds));
+            }
+        }
+        self.occupied = Some(match self.occupied {
+            Some(((first_x, first_y), (last_x, last_y))) => ((first_x.min(min_x), first_y.min(min_y)), (last_x.max(max_x), last_y.max(max_y))),
+            None => ((min_x, min_y), (max_x, max_y)),
+        });
+    }
+
+    /// Replaces the content of the grid with the given entities
+    pub fn rebuild(&mut self, entities: impl IntoIterator<Item = (Entity, Rect)>) {
+        self.clear();
+        for (entity, bounds) in entities {
+            self.insert(entity, bounds);
+        }
+    }
+
+    /// The entities whose bounds overlap the given area, each listed once
+    pub fn query_rect(&self, area: Rect) -> Vec<Entity> {
+        let mut seen = BitSet::new();
+        let mut found = Vec::new();
+        let ((min_x, min_y), (max_x, max_y)) = self.cell_range(area);
+        for cell_y in min_y..=max_y {
+            for cell_x in min_x..=max_x {
+                for &(entity, bounds) in self.cell((cell_x, cell_y)) {
+                    if !seen.contains(entity.id()) && bounds.has_intersection(area) {
+                        seen.add(entity.id());
+                        found.push(entity);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// The entity accepted by `filter` whose center is the closest to `point`, if there is one
+    /// within `max_distance`
+    pub fn nearest(&self, point: Point, max_distance: u32, mut filter: impl FnMut(Entity) -> bool) -> Option<Entity> {
+        let max_distance = i64::from(max_distance);
+        let max_distance_squared = max_distance.saturating_mul(max_distance);
+        let (center_x, center_y) = self.cell_of(point);
+        let ((first_x, first_y), (last_x, last_y)) = self.occupied?;
+        // Rings beyond the last occupied cell are empty, however far `max_distance` goes
+        let occupied_ring = [first_x - center_x, last_x - center_x, first_y - center_y, last_y - center_y]
+            .into_iter()
+            .map(|offset| i64::from(offset).abs())
+            .max()
+            .unwrap_or(0);
+        let max_ring = (max_distance / i64::from(self.cell_size) + 1).min(occupied_ring);
+        let mut nearest: Option<(i64, Entity)> = None;
+
+        // Look at rings of cells further and further away from the point. Every point of ring
+        // `r + 1` is at least `r * cell_size` away from the point, so once something closer than
+        // that has been found, the remaining rings can be skipped.
+        for ring in 0..=max_ring as i32 {
+            for (cell_x, cell_y) in ring_cells((center_x, center_y), ring) {
+                for &(entity, bounds) in self.cell((cell_x, cell_y)) {
+                    let offset = bounds.center() - point;
+                    let distance_squared = i64::from(offset.x()).pow(2) + i64::from(offset.y()).pow(2);
+                    if distance_squared > max_distance_squared {
+                        continue;
+                    }
+                    let is_closer = !matches!(nearest, Some((nearest_distance, _)) if nearest_distance <= distance_squared);
+                    if is_closer && filter(entity) {
+                        nearest = Some((distance_squared, entity));
+                    }
+                }
+            }
+            let searched_radius = i64::from(ring) * i64::from(self.cell_size);
+            if let Some((nearest_distance, _)) = nearest {
+                if nearest_distance <= searched_radius.pow(2) {
+                    break;
+                }
+            }
+        }
+        nearest.map(|(_, entity)| entity)
+    }
+
+    /// The entity with the given component whose center is the closest to `point`, if there is
+    /// one within `max_distance`
+    pub fn nearest_with<C: Component>(&self, point: Point, max_distance: u32, storage: &ReadStorage<C>) -> Option<Entity> {
+        self.nearest(point, max_distance, |entity| storage.contains(entity))
+    }
+
+    fn cell(&self, cell: (i32, i32)) -> &[(Entity, Rect)] {
+        self.cells.get(&cell).map_or(&[], Vec::as_slice)
+    }
+
+    fn cell_of(&self, point: Point) -> (i32, i32) {
+        (point.x().div_euclid(self.cell_size), point.y().div_euclid(self.cell_size))
+    }
+
+    /// The first and last cells overlapped by the given bounds
+    fn cell_range(&self, bounds: Rect) -> ((i32, i32), (i32, i32)) {
+        let top_left = self.cell_of(bounds.top_left());
+        // The right and bottom edges are exclusive
+        let bottom_right = self.cell_of(Point::new(bounds.right() - 1, bounds.bottom() - 1));
+        (top_left, bottom_right)
+    }
+}
+
+/// The cells at exactly `ring` cells (horizontally or vertically) from `center`
+fn ring_cells((center_x, center_y): (i32, i32), ring: i32) -> impl Iterator<Item = (i32, i32)> {
+    (-ring..=ring).flat_map(move |dy| (-ring..=ring).map(move |dx| (dx, dy)))
+        .filter(move |&(dx, dy)| dx.abs() == ring || dy.abs() == ring)
+        .map(move |(dx, dy)| (center_x + dx, center_y + dy))
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{World, WorldExt, Builder};
+
+    use super::*;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    #[test]
+    fn clear_drops_cells_left_empty() {
+        let entities = entities(1);
+        let mut grid = SpatialGrid::new(10);
+        for x in 0..100 {
+            grid.rebuild([(entities[0], Rect::new(x * 10, 0, 5, 5))]);
+        }
+        // The cell occupied now and the one occupied before the last rebuild
+        assert_eq!(grid.cells.len(), 2);
+        assert_eq!(grid.query_rect(Rect::new(990, 0, 10, 10)), vec![entities[0]]);
+    }
+
+    #[test]
+    fn nearest_within_a_huge_distance() {
+        let entities = entities(2);
+        let mut grid = SpatialGrid::new(10);
+        grid.insert(entities[0], Rect::from_center((500, 0), 4, 4));
+        grid.insert(entities[1], Rect::from_center((-40, 30), 4, 4));
+        assert_eq!(grid.nearest(Point::new(0, 0), u32::MAX, |_| true), Some(entities[1]));
+        assert_eq!(grid.nearest(Point::new(0, 0), u32::MAX, |entity| entity == entities[0]), Some(entities[0]));
+        assert_eq!(grid.nearest(Point::new(0, 0), 40, |_| true), None);
+        assert_eq!(SpatialGrid::new(10).nearest(Point::new(0, 0), u32::MAX, |_| true), None);
+    }
+}
@@ -0,0 +1,151 @@
+//! Sprite sheet descriptors name the animations found in a sprite sheet image, for characters
+//! whose sprite sheets do not follow the layout expected by
+//! `DirectionalAnimations::standard_walking_animations`. Each animation lists the region of the
+//! image and the duration of every one of its frames, along with its `LoopMode`.
+//!
+//! See `assets/sprites/reaper.ron` for an example of the format.
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use sdl2::rect::Rect;
+use serde::Deserialize;
+
+use crate::assets::TextureHandle;
+use crate::components::{Animation, Frame, LoopMode, Sprite};
+
+/// The animations of a sprite sheet, as written in a sprite sheet descriptor file
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpriteSheetDescriptor {
+    /// The animations of the sprite sheet, by name
+    pub animations: HashMap<String, AnimationDescriptor>,
+}
+
+/// A single animation of a sprite sheet
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnimationDescriptor {
+    /// What the animation does once it reaches its last frame
+    #[serde(default)]
+    pub loop_mode: LoopMode,
+    /// The duration of the frames that do not have their own, in milliseconds
+    #[serde(default)]
+    pub frame_duration_ms: Option<u64>,
+    /// The frames of the animation, in order
+    pub frames: Vec<FrameDescriptor>,
+}
+
+/// A single frame of an animation
+#[derive(Debug, Clone, Deserialize)]
+pub struct FrameDescriptor {
+    /// The `(x, y, width, height)` region of the sprite sheet to draw
+    pub region: (i32, i32, u32, u32),
+    /// How long the frame is shown, in milliseconds. Defaults to the `frame_duration_ms` of the
+    /// animation.
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// The names of the events sent when the frame starts, e.g. `"footstep"`
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// The reasons a sprite sheet descriptor file can fail to load
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    /// The descriptor file could not be read
+    Io {
+        /// The path of the descriptor file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The descriptor file is not valid RON or does not match the descriptor format
+    Parse {
+        /// The path of the descriptor file
+        path: PathBuf,
+        /// The underlying error, including the position of the error in the file
+        source: ron::error::SpannedError,
+    },
+    /// The descriptor file is well-formed but describes animations that cannot be played
+    Invalid {
+        /// The path of the descriptor file
+        path: PathBuf,
+        /// What is wrong with the descriptor
+        message: String,
+    },
+}
+
+impl fmt::Display for SpriteSheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpriteSheetError::Io {path, source} => write!(f, "Could not read sprite sheet descriptor {}: {}", path.display(), source),
+            SpriteSheetError::Parse {path, source} => write!(f, "Could not parse sprite sheet descriptor {}: {}", path.display(), source),
+            SpriteSheetError::Invalid {path, message} => write!(f, "Invalid sprite sheet descriptor {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for SpriteSheetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpriteSheetError::Io {source, ..} => Some(source),
+            SpriteSheetError::Parse {source, ..} => Some(source),
+            SpriteSheetError::Invalid {..} => None,
+        }
+    }
+}
+
+impl SpriteSheetDescriptor {
+    /// Reads and validates the descriptor file at the given path
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SpriteSheetError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|source| SpriteSheetError::Io {path: path.to_path_buf(), source})?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses and validates a descriptor. The `path` is only used in error messages.
+    pub fn parse(source: &str, path: impl AsRef<Path>) -> Result<Self, SpriteSheetError> {
+        let path = path.as_ref();
+        let descriptor: SpriteSheetDescriptor = ron::from_str(source)
+            .map_err(|source| SpriteSheetError::Parse {path: path.to_path_buf(), source})?;
+        descriptor.validate()
+            .map_err(|message| SpriteSheetError::Invalid {path: path.to_path_buf(), message})?;
+        Ok(descriptor)
+    }
+
+    /// The animation with the given name, drawn from the given texture
+    pub fn animation(&self, name: &str, texture: TextureHandle) -> Option<Animation> {
+        let AnimationDescriptor {loop_mode, frame_duration_ms, frames} = self.animations.get(name)?;
+        let frames = frames.iter()
+            .map(|FrameDescriptor {region: (x, y, width, height), duration_ms, events}| Frame {
+                sprite: Sprite {texture, region: Rect::new(*x, *y, *width, *height)},
+                duration: Duration::from_millis(duration_ms.or(*frame_duration_ms).expect("frame durations are validated on load")),
+                events: events.clone(),
+            })
+            .collect();
+        Some(Animation::new(frames, *loop_mode))
+    }
+
+    /// Checks everything that the file format itself cannot express
+    fn validate(&self) -> Result<(), String> {
+        for (name, animation) in &self.animations {
+            if animation.frames.is_empty() {
+                return Err(format!("animation `{}` must have at least one frame", name));
+            }
+            for (i, frame) in animation.frames.iter().enumerate() {
+                let (_, _, width, height) = frame.region;
+                if width == 0 || height == 0 {
+                    return Err(format!("frame #{} of animation `{}` must have a non-empty region", i, name));
+                }
+                if frame.duration_ms.or(animation.frame_duration_ms).is_none() {
+                    return Err(format!("frame #{} of animation `{}` has no duration and the animation has no frame_duration_ms", i, name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
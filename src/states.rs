@@ -0,0 +1,93 @@
+//! The screens a game goes through, from the title screen to the end of a game, are kept in a
+//! `StateStack`. The state on top of the stack is the current one. States pushed on top of
+//! another one, like the pause screen, cover it without ending it, so popping them resumes the
+//! state underneath exactly where it was left.
+//!
+//! Whoever drives the stack reacts to states starting and ending through `StateHooks`, e.g. to
+//! restart the game when leaving the game over screen.
+
+/// A screen of the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    /// Waiting for the player to start
+    Title,
+    /// The world is being simulated
+    Playing,
+    /// The world is frozen until the game is resumed
+    Paused,
+    /// The player lost
+    GameOver,
+    /// The player won
+    Victory,
+}
+
+impl GameState {
+    /// Whether the world is simulated while in this state
+    pub fn is_simulated(self) -> bool {
+        self == GameState::Playing
+    }
+}
+
+/// Called by a `StateStack` whenever a state starts or ends. Both hooks do nothing by default.
+pub trait StateHooks {
+    /// Called when the given state becomes the current state, but not when it becomes current
+    /// again because the states pushed on top of it were popped
+    fn on_enter(&mut self, _state: GameState) {}
+
+    /// Called when the given state is removed from the stack, but not when another state is
+    /// pushed on top of it
+    fn on_exit(&mut self, _state: GameState) {}
+}
+
+/// A stack of states which is never empty
+#[derive(Debug, Clone)]
+pub struct StateStack {
+    states: Vec<GameState>,
+}
+
+impl StateStack {
+    /// A stack containing the given state, which is entered right away
+    pub fn new(initial: GameState, hooks: &mut impl StateHooks) -> Self {
+        hooks.on_enter(initial);
+        Self {states: vec![initial]}
+    }
+
+    /// The state on top of the stack
+    pub fn current(&self) -> GameState {
+        *self.states.last().expect("the state stack is never empty")
+    }
+
+    /// Covers the current state with the given one
+    pub fn push(&mut self, state: GameState, hooks: &mut impl StateHooks) {
+        self.states.push(state);
+        hooks.on_enter(state);
+    }
+
+    /// Ends the current state and resumes the one below it. The last state of the stack cannot be
+    /// popped; `None` is returned instead.
+    pub fn pop(&mut self, hooks: &mut impl StateHooks) -> Option<GameState> {
+        if self.states.len() == 1 {
+            return None;
+        }
+        let state = self.states.pop()?;
+        hooks.on_exit(state);
+        Some(state)
+    }
+
+    /// Ends the current state and replaces it with the given one
+    pub fn switch(&mut self, state: GameState, hooks: &mut impl StateHooks) {
+        let previous = self.states.pop().expect("the state stack is never empty");
+        hooks.on_exit(previous);
+        self.states.push(state);
+        hooks.on_enter(state);
+    }
+
+    /// Ends every state of the stack, from the top down, and starts over from the given state
+    pub fn reset(&mut self, state: GameState, hooks: &mut impl StateHooks) {
+        while let Some(previous) = self.states.pop() {
+            hooks.on_exit(previous);
+        }
+        self.states.push(state);
+        hooks.on_enter(state);
+    }
+}
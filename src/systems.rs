@@ -1,11 +1,17 @@
 mod keyboard;
 mod movement;
+mod route_following;
 mod animator;
 mod ai;
 mod win_lose_checker;
+mod spatial_indexer;
+mod camera_follow;
 
 pub use keyboard::*;
 pub use movement::*;
+pub use route_following::*;
 pub use animator::*;
 pub use ai::*;
-pub use win_lose_checker::*;
\ No newline at end of file
+pub use win_lose_checker::*;
+pub use spatial_indexer::*;
+pub use camera_follow::*;
\ No newline at end of file
@@ -1,41 +1,327 @@
-use std::time::Instant;
+use std::time::Duration;
 
-use rand::{Rng, thread_rng};
-use specs::{System, SystemData, WriteStorage, Join, World, prelude::ResourceId};
+use rand::Rng;
+use sdl2::rect::Point;
+use specs::{System, SystemData, Entity, Entities, Read, ReadExpect, WriteExpect, ReadStorage, WriteStorage, Join, World, prelude::ResourceId};
 
 use crate::direction::Direction;
-use crate::components::{Enemy, Velocity};
+use crate::vector::Vector;
+use crate::pathfinding::{Pathfinder, PATH_CELL_SIZE};
+use crate::behaviour_tree::{Agent, Action, BlackboardValue, Condition, Status, Target};
+use crate::resources::{GameRng, GameTime, TimeDelta, CollisionEvents, CollisionEvent};
+use crate::components::{Enemy, IdleBehaviour, Reaction, EnemyMode, Player, Position, Locomotion, Route, Behaviour, Blackboard, AnimationStateMachine};
 
+/// How close the center of an enemy must be to the center of the player it sees to attack them,
+/// in pixels
+const ATTACK_RANGE: f32 = 64.0;
+
+/// Makes enemies follow their behaviour: wander around by randomly changing their direction,
+/// guard their post or patrol, and chase or flee from the player when they see them. Enemies that
+/// walk somewhere are given a `Route` to follow. Enemies with a `Behaviour` tick their behaviour
+/// tree instead of reacting to the player on their own. Enemies that are not fleeing attack the
+/// player once they are within reach, which plays the attack animation of their
+/// `AnimationStateMachine`.
 pub struct AI;
 
+/// The data read and written by the `AI` system
 #[derive(SystemData)]
 pub struct AIData<'a> {
+    entities: Entities<'a>,
     enemies: WriteStorage<'a, Enemy>,
-    velocities: WriteStorage<'a, Velocity>,
+    behaviours: ReadStorage<'a, Behaviour>,
+    blackboards: WriteStorage<'a, Blackboard>,
+    players: ReadStorage<'a, Player>,
+    positions: ReadStorage<'a, Position>,
+    locomotions: WriteStorage<'a, Locomotion>,
+    routes: WriteStorage<'a, Route>,
+    animation_state_machines: WriteStorage<'a, AnimationStateMachine>,
+    pathfinder: ReadExpect<'a, Pathfinder>,
+    rng: WriteExpect<'a, GameRng>,
+    collision_events: Read<'a, CollisionEvents>,
+    time_delta: ReadExpect<'a, TimeDelta>,
+    game_time: ReadExpect<'a, GameTime>,
 }
 
 impl<'a> System<'a> for AI {
     type SystemData = AIData<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let AIData {mut enemies, mut velocities} = data;
-
-        let mut rng = thread_rng();
-        for (enemy, velocity) in (&mut enemies, &mut velocities).join() {
-            if enemy.direction_timer.elapsed() >= enemy.direction_change_delay {
-                velocity.direction = match rng.gen_range(1..101) {
-                    // 60% probability of staying in the same direction; 30% chance of changing to some other random direction
-                    1..=60 => velocity.direction,
-                    61..=70 => Direction::Up,
-                    71..=80 => Direction::Down,
-                    81..=90 => Direction::Left,
-                    91..=100 => Direction::Right,
-                    _ => unreachable!(),
+        let AIData {
+            entities,
+            mut enemies,
+            behaviours,
+            mut blackboards,
+            players,
+            positions,
+            mut locomotions,
+            mut routes,
+            mut animation_state_machines,
+            pathfinder,
+            mut rng,
+            collision_events,
+            time_delta,
+            game_time,
+        } = data;
+        let TimeDelta(time_elapsed) = *time_delta;
+        let GameTime(now) = *game_time;
+        let CollisionEvents(collision_events) = &*collision_events;
+        let player = (&players, &positions).join().map(|(_, &Position(position))| position.to_point()).next();
+
+        for (entity, enemy, &Position(position), locomotion, behaviour, blackboard) in (&entities, &mut enemies, &positions, &mut locomotions, behaviours.maybe(), (&mut blackboards).maybe()).join() {
+            let position = position.to_point();
+            let in_sight = player.filter(|&player| {
+                distance(position, player) <= enemy.sight_radius as f32
+                    && pathfinder.grid().line_of_sight(position, player)
+            });
+            if let Some(player) = in_sight {
+                enemy.last_seen = Some((player, now));
+                if enemy.mode != EnemyMode::Fleeing && distance(position, player) <= ATTACK_RANGE {
+                    if let Some(state_machine) = animation_state_machines.get_mut(entity) {
+                        state_machine.flags.attacking = true;
+                    }
+                }
+            }
+            let remembered = enemy.last_seen
+                .filter(|&(_, seen)| now.saturating_sub(seen) <= enemy.memory)
+                .map(|(point, _)| point);
+            // Wandering enemies that ran into something during the last update turn around
+            let collision = collision_events.iter()
+                .find(|event| event.entity == entity)
+                .map(|&CollisionEvent {direction, ..}| direction);
+
+            if let (Some(Behaviour(tree)), Some(blackboard)) = (behaviour, blackboard) {
+                let mut agent = EnemyAgent {
+                    entity,
+                    enemy,
+                    position,
+                    locomotion,
+                    routes: &mut routes,
+                    rng: &mut rng,
+                    player,
+                    in_sight,
+                    remembered,
+                    collision,
+                    now,
                 };
+                tree.tick(&mut agent, blackboard, time_elapsed);
+                continue;
+            }
 
-                // Reset the direction timer
-                enemy.direction_timer = Instant::now();
+            let mode = match (enemy.mode, remembered) {
+                (EnemyMode::Idle | EnemyMode::Returning, _) if in_sight.is_some() => match enemy.reaction {
+                    Reaction::Chase => EnemyMode::Chasing,
+                    Reaction::Flee => EnemyMode::Fleeing,
+                    Reaction::Ignore => enemy.mode,
+                },
+                (EnemyMode::Chasing | EnemyMode::Fleeing, None) => EnemyMode::Returning,
+                (mode, _) => mode,
+            };
+            if mode != enemy.mode {
+                enter_mode(entity, enemy, mode, position, locomotion, &mut routes);
+            }
+
+            match (enemy.mode, remembered) {
+                (EnemyMode::Idle, _) => idle(entity, enemy, locomotion, &mut routes, &mut *rng, collision, now),
+                (EnemyMode::Chasing, Some(target)) => {
+                    // The route is planned again once the player has moved away from its end
+                    let planned = routes.get(entity).is_some_and(|route| distance(route.destination, target) <= PATH_CELL_SIZE as f32);
+                    if !planned && distance(position, target) > PATH_CELL_SIZE as f32 {
+                        walk_to(entity, target, &mut routes);
+                    }
+                },
+                (EnemyMode::Fleeing, Some(threat)) => flee(locomotion, position, threat),
+                // A returning enemy without a route is back at its post, or as close as it can get
+                (EnemyMode::Returning, _) => if !routes.contains(entity) {
+                    enter_mode(entity, enemy, EnemyMode::Idle, position, locomotion, &mut routes);
+                },
+                (EnemyMode::Chasing | EnemyMode::Fleeing, None) => unreachable!("enemies give up once they forget the player"),
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// An enemy ticked by its behaviour tree, along with what it knows about the player
+struct EnemyAgent<'e, 'a> {
+    entity: Entity,
+    enemy: &'e mut Enemy,
+    position: Point,
+    locomotion: &'e mut Locomotion,
+    routes: &'e mut WriteStorage<'a, Route>,
+    rng: &'e mut GameRng,
+    /// Where the player is, seen or not
+    player: Option<Point>,
+    in_sight: Option<Point>,
+    remembered: Option<Point>,
+    collision: Option<Direction>,
+    now: Duration,
+}
+
+impl<'e, 'a> Agent for EnemyAgent<'e, 'a> {
+    fn check(&mut self, condition: &Condition, _blackboard: &Blackboard) -> bool {
+        match *condition {
+            Condition::PlayerInSight => self.in_sight.is_some(),
+            Condition::PlayerRemembered => self.remembered.is_some(),
+            Condition::PlayerWithin(radius) => self.player.is_some_and(|player| distance(self.position, player) <= radius as f32),
+            Condition::AtPost => distance(self.position, self.enemy.post) <= PATH_CELL_SIZE as f32,
+            Condition::Chance(probability) => self.rng.gen::<f32>() < probability,
+            Condition::IsSet(_) | Condition::Equals(..) => unreachable!("the tree checks its blackboard itself"),
+        }
+    }
+
+    fn act(&mut self, action: &Action, blackboard: &mut Blackboard, running_for: Option<Duration>) -> Status {
+        let EnemyAgent {entity, ref mut enemy, position, ref mut locomotion, ref mut routes, ref mut rng, remembered, collision, now, ..} = *self;
+        match action {
+            Action::Idle => {
+                if running_for.is_none() {
+                    start_idle(entity, enemy, locomotion, routes);
+                }
+                idle(entity, enemy, locomotion, routes, &mut **rng, collision, now);
+                Status::Running
+            },
+            Action::Wander => {
+                if running_for.is_none() {
+                    routes.remove(entity);
+                    locomotion.heading = Vector::ZERO;
+                }
+                wander(enemy, locomotion, &mut **rng, collision, now);
+                Status::Running
+            },
+            Action::Stand => {
+                routes.remove(entity);
+                locomotion.heading = Vector::ZERO;
+                Status::Success
+            },
+            Action::WalkTo(target) => {
+                let target = match target {
+                    Target::Post => Some(enemy.post),
+                    Target::Player => remembered,
+                    Target::Key(name) => match blackboard.values.get(name) {
+                        Some(&BlackboardValue::Point(x, y)) => Some(Point::new(x, y)),
+                        _ => None,
+                    },
+                };
+                let target = match target {
+                    Some(target) => target,
+                    None => return Status::Failure,
+                };
+                if distance(position, target) <= PATH_CELL_SIZE as f32 {
+                    routes.remove(entity);
+                    locomotion.heading = Vector::ZERO;
+                    return Status::Success;
+                }
+                match routes.get(entity) {
+                    // The route is planned again once the target has moved away from its end
+                    Some(route) if distance(route.destination, target) <= PATH_CELL_SIZE as f32 => Status::Running,
+                    // The route ended as close to the target as the enemy can get
+                    None if running_for.is_some() => Status::Success,
+                    _ => {
+                        walk_to(entity, target, routes);
+                        Status::Running
+                    },
+                }
+            },
+            Action::Flee => match remembered {
+                Some(threat) => {
+                    routes.remove(entity);
+                    flee(locomotion, position, threat);
+                    Status::Running
+                },
+                None => Status::Failure,
+            },
+            Action::Wait(_) | Action::Set(..) | Action::Clear(_) => unreachable!("the tree performs these actions itself"),
+        }
+    }
+}
+
+/// Switches the enemy to the given mode, and sets it off on the way it takes in that mode
+fn enter_mode(entity: Entity, enemy: &mut Enemy, mode: EnemyMode, position: Point, locomotion: &mut Locomotion, routes: &mut WriteStorage<Route>) {
+    routes.remove(entity);
+    locomotion.heading = Vector::ZERO;
+    let at_post = distance(position, enemy.post) <= PATH_CELL_SIZE as f32;
+    enemy.mode = match (mode, &enemy.idle) {
+        // Patrols carry on from the waypoint they were walking to, wherever the enemy is
+        (EnemyMode::Returning, IdleBehaviour::Patrol(_)) => EnemyMode::Idle,
+        (EnemyMode::Returning, _) if at_post => EnemyMode::Idle,
+        _ => mode,
+    };
+    match enemy.mode {
+        EnemyMode::Idle => start_idle(entity, enemy, locomotion, routes),
+        EnemyMode::Returning => walk_to(entity, enemy.post, routes),
+        EnemyMode::Chasing | EnemyMode::Fleeing => {},
+    }
+}
+
+/// Sets the enemy off on its `IdleBehaviour`, after doing something else
+fn start_idle(entity: Entity, enemy: &mut Enemy, locomotion: &mut Locomotion, routes: &mut WriteStorage<Route>) {
+    routes.remove(entity);
+    locomotion.heading = Vector::ZERO;
+    match enemy.idle {
+        IdleBehaviour::Wander | IdleBehaviour::Guard => {},
+        IdleBehaviour::Patrol(_) => walk_to(entity, patrol_waypoint(enemy), routes),
+    }
+}
+
+/// Follows the `IdleBehaviour` of the enemy for an update
+fn idle(entity: Entity, enemy: &mut Enemy, locomotion: &mut Locomotion, routes: &mut WriteStorage<Route>, rng: &mut impl Rng, collision: Option<Direction>, now: Duration) {
+    match &enemy.idle {
+        IdleBehaviour::Wander => wander(enemy, locomotion, rng, collision, now),
+        IdleBehaviour::Guard => {},
+        // A patrolling enemy without a route has reached its waypoint
+        IdleBehaviour::Patrol(waypoints) => if !routes.contains(entity) {
+            enemy.next_waypoint = (enemy.next_waypoint + 1) % waypoints.len();
+            walk_to(entity, patrol_waypoint(enemy), routes);
+        },
+    }
+}
+
+/// Randomly changes the direction of a wandering enemy every once in a while, and turns it around
+/// when it runs into something
+fn wander(enemy: &mut Enemy, locomotion: &mut Locomotion, rng: &mut impl Rng, collision: Option<Direction>, now: Duration) {
+    if let Some(direction) = collision {
+        locomotion.heading = direction.opposite().into_vector();
+        enemy.direction_changed = now;
+        return;
+    }
+    // An enemy that just started wandering picks a direction at once
+    let starting = locomotion.heading == Vector::ZERO;
+    if !starting && now.saturating_sub(enemy.direction_changed) < enemy.direction_change_delay {
+        return;
+    }
+    let roll = if starting { rng.gen_range(61..101) } else { rng.gen_range(1..101) };
+    locomotion.heading = match roll {
+        // 60% probability of staying in the same direction; 30% chance of changing to some other random direction
+        1..=60 => locomotion.heading,
+        61..=70 => Direction::Up.into_vector(),
+        71..=80 => Direction::Down.into_vector(),
+        81..=90 => Direction::Left.into_vector(),
+        91..=100 => Direction::Right.into_vector(),
+        _ => unreachable!(),
+    };
+
+    // Reset the direction timer
+    enemy.direction_changed = now;
+}
+
+/// The waypoint a patrolling enemy is walking to
+fn patrol_waypoint(enemy: &Enemy) -> Point {
+    match &enemy.idle {
+        IdleBehaviour::Patrol(waypoints) => waypoints[enemy.next_waypoint].into(),
+        _ => enemy.post,
+    }
+}
+
+/// Points the heading of the enemy away from the threat
+fn flee(locomotion: &mut Locomotion, position: Point, threat: Point) {
+    locomotion.heading = (Vector::from_point(position) - Vector::from_point(threat)).normalized();
+}
+
+/// Gives the enemy a route to the given point
+fn walk_to(entity: Entity, destination: Point, routes: &mut WriteStorage<Route>) {
+    routes.insert(entity, Route::new(destination))
+        .expect("failed to give an enemy a route");
+}
+
+/// The straight line distance between two points
+fn distance(point: Point, other: Point) -> f32 {
+    (Vector::from_point(point) - Vector::from_point(other)).length()
+}
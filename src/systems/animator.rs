@@ -1,18 +1,23 @@
-use std::time::Instant;
+use specs::{System, SystemData, Entity, Entities, ReadExpect, ReadStorage, WriteStorage, Write, Join, World, prelude::ResourceId};
 
-use specs::{System, SystemData, Entities, ReadStorage, WriteStorage, Join, World, prelude::ResourceId};
-
-use crate::components::{Velocity, Animation, Sprite, MovementAnimations};
+use crate::direction::Direction;
+use crate::resources::{GameTime, AnimationEvents, AnimationEvent};
+use crate::components::{Velocity, Animation, Sprite, AnimationStateMachine, AnimationState};
 
+/// Moves every `AnimationStateMachine` to the state matching its entity, plays the `Animation` of
+/// each entity and reports the events of the frames it reaches in the `AnimationEvents` resource
 pub struct Animator;
 
+/// The data read and written by the `Animator` system
 #[derive(SystemData)]
 pub struct AnimatorData<'a> {
     entities: Entities<'a>,
     velocities: ReadStorage<'a, Velocity>,
-    movement_animations: ReadStorage<'a, MovementAnimations>,
+    state_machines: WriteStorage<'a, AnimationStateMachine>,
     animations: WriteStorage<'a, Animation>,
     sprites: WriteStorage<'a, Sprite>,
+    animation_events: Write<'a, AnimationEvents>,
+    game_time: ReadExpect<'a, GameTime>,
 }
 
 impl<'a> System<'a> for Animator {
@@ -22,34 +27,122 @@ impl<'a> System<'a> for Animator {
         let AnimatorData {
             entities,
             velocities,
-            movement_animations,
+            mut state_machines,
             mut animations,
             mut sprites,
+            mut animation_events,
+            game_time,
         } = data;
+        let GameTime(now) = *game_time;
+        let AnimationEvents(animation_events) = &mut *animation_events;
+        animation_events.clear();
+
+        for (entity, velocity, state_machine) in (&*entities, velocities.maybe(), &mut state_machines).join() {
+            let current_animation = animations.get(entity);
+            // A one-shot attack goes back to whatever the entity was doing once it has played
+            if state_machine.state.plays_once() && current_animation.is_some_and(Animation::is_finished) {
+                state_machine.flags.attacking = false;
+            }
 
-        for (entity, &Velocity {speed, direction}, move_animations) in (&*entities, &velocities, &movement_animations).join() {
-            let anim_frames = animations.get(entity).map(|anim| anim.frames.clone());
-            if speed == 0 && anim_frames.is_some() {
-                animations.remove(entity);
+            let direction = velocity.and_then(|&Velocity(velocity)| Direction::of(velocity));
+            let state = state_machine.desired_state(direction.is_some());
+            let mut facing = state_machine.facing;
+            if let (AnimationState::Idle | AnimationState::Walk, Some(direction)) = (state, direction) {
+                facing = direction;
+            }
+            if current_animation.is_some() && state == state_machine.state && facing == state_machine.facing {
                 continue;
             }
-            let dir_anim = move_animations.animation_for(direction);
-            let needs_update = match anim_frames {
-                Some(anim_frames) => anim_frames != dir_anim.frames,
-                None => true,
-            };
-            if needs_update {
-                animations.insert(entity, dir_anim.clone())
+
+            if let Some(animation) = state_machine.animation(state, facing, now) {
+                state_machine.state = state;
+                state_machine.facing = facing;
+                start_frame(entity, &animation, &mut sprites, animation_events);
+                animations.insert(entity, animation)
                     .expect("failed to update animation");
             }
         }
 
-        for (anim, sprite) in (&mut animations, &mut sprites).join() {
-            if anim.frame_timer.elapsed() >= anim.frames[anim.current_frame].duration {
-                anim.current_frame = (anim.current_frame + 1) % anim.frames.len();
-                anim.frame_timer = Instant::now();
-                *sprite = anim.frames[anim.current_frame].sprite.clone();
+        for (entity, anim) in (&*entities, &mut animations).join() {
+            if now.saturating_sub(anim.frame_started) >= anim.frames[anim.current_frame].duration {
+                anim.frame_started = now;
+                if anim.advance() {
+                    start_frame(entity, anim, &mut sprites, animation_events);
+                }
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Shows the current frame of the animation and sends its events
+fn start_frame(entity: Entity, animation: &Animation, sprites: &mut WriteStorage<Sprite>, animation_events: &mut Vec<AnimationEvent>) {
+    let frame = &animation.frames[animation.current_frame];
+    if let Some(sprite) = sprites.get_mut(entity) {
+        *sprite = frame.sprite.clone();
+    }
+    animation_events.extend(frame.events.iter().map(|name| AnimationEvent {entity, name: name.clone()}));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use sdl2::rect::Rect;
+    use specs::{Builder, RunNow, WorldExt};
+
+    use super::*;
+    use crate::assets::AssetManager;
+    use crate::components::{Frame, LoopMode, DirectionalAnimations};
+
+    /// An animation of frames lasting 100ms each, whose sprites tell the frames apart by their
+    /// horizontal position
+    fn animation(frames: i32, loop_mode: LoopMode) -> DirectionalAnimations {
+        let texture = AssetManager::default().texture("test.png");
+        let animation = Animation::new((0..frames).map(|frame| Frame {
+            sprite: Sprite {texture, region: Rect::new(frame * 10, 0, 10, 10)},
+            duration: Duration::from_millis(100),
+            events: Vec::new(),
+        }).collect(), loop_mode);
+        DirectionalAnimations {up: animation.clone(), down: animation.clone(), left: animation.clone(), right: animation}
+    }
+
+    fn world_with(state_machine: AnimationStateMachine) -> (World, Entity) {
+        let mut world = World::new();
+        System::setup(&mut Animator, &mut world);
+        world.insert(GameTime::default());
+        let entity = world.create_entity()
+            .with(state_machine)
+            .build();
+        (world, entity)
+    }
+
+    /// Advances the game time by the given number of milliseconds and runs the `Animator`
+    fn animate(world: &mut World, milliseconds: u64) {
+        world.write_resource::<GameTime>().0 += Duration::from_millis(milliseconds);
+        Animator.run_now(world);
+        world.maintain();
+    }
+
+    #[test]
+    fn attack_plays_once_then_goes_back_to_idle() {
+        let animations = HashMap::from([
+            (AnimationState::Walk, animation(3, LoopMode::Loop)),
+            (AnimationState::Attack, animation(2, LoopMode::Once)),
+        ]);
+        let mut state_machine = AnimationStateMachine::new(animations, Direction::Down);
+        state_machine.flags.attacking = true;
+        let (mut world, entity) = world_with(state_machine);
+
+        animate(&mut world, 0);
+        assert_eq!(world.read_storage::<AnimationStateMachine>().get(entity).unwrap().state, AnimationState::Attack);
+        // The second and last frame of the attack starts
+        animate(&mut world, 100);
+        assert_eq!(world.read_storage::<AnimationStateMachine>().get(entity).unwrap().state, AnimationState::Attack);
+        animate(&mut world, 0);
+        let state_machines = world.read_storage::<AnimationStateMachine>();
+        let state_machine = state_machines.get(entity).unwrap();
+        assert_eq!(state_machine.state, AnimationState::Idle);
+        assert!(!state_machine.flags.attacking);
+    }
+}
@@ -0,0 +1,38 @@
+use sdl2::rect::Rect;
+use specs::{System, SystemData, Entities, ReadExpect, ReadStorage, Write, Join, World, prelude::ResourceId};
+
+use crate::camera::Camera;
+use crate::resources::TimeDelta;
+use crate::components::{BoundingBox, Player};
+
+/// Moves the `Camera` towards its target, or towards the player when it has no target
+pub struct CameraFollow {
+    /// The area of the world the camera is allowed to show
+    pub world_bounds: Rect,
+}
+
+/// The data read and written by the `CameraFollow` system
+#[derive(SystemData)]
+pub struct CameraFollowData<'a> {
+    entities: Entities<'a>,
+    players: ReadStorage<'a, Player>,
+    bounding_boxes: ReadStorage<'a, BoundingBox>,
+    camera: Write<'a, Camera>,
+    time_delta: ReadExpect<'a, TimeDelta>,
+}
+
+impl<'a> System<'a> for CameraFollow {
+    type SystemData = CameraFollowData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let CameraFollowData {entities, players, bounding_boxes, mut camera, time_delta} = data;
+        let TimeDelta(time_elapsed) = *time_delta;
+
+        let target = camera.target
+            .or_else(|| (&entities, &players).join().map(|(entity, _)| entity).next());
+        let followed = target.and_then(|entity| {
+            bounding_boxes.get(entity).map(|&BoundingBox(bounds)| (entity, bounds.center()))
+        });
+        camera.update(followed, self.world_bounds, time_elapsed);
+    }
+}
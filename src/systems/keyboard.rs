@@ -1,15 +1,22 @@
-use specs::{System, SystemData, Read, ReadStorage, WriteStorage, Join, World, prelude::ResourceId};
+use sdl2::rect::Point;
+use specs::{System, SystemData, Entities, Read, ReadStorage, WriteStorage, Join, World, prelude::ResourceId};
 
+use crate::vector::Vector;
 use crate::resources::KeyboardEvent;
-use crate::components::{Player, Velocity};
+use crate::components::{Player, Locomotion, Route};
 use KeyboardEvent::*;
 
+/// Steers the `Locomotion` of the player from the `KeyboardEvent` of the current update. Moving
+/// to a point gives the player a `Route` to follow, which any other movement cancels.
 pub struct Keyboard;
 
+/// The data read and written by the `Keyboard` system
 #[derive(SystemData)]
 pub struct KeyboardData<'a> {
+    entities: Entities<'a>,
     players: ReadStorage<'a, Player>,
-    velocities: WriteStorage<'a, Velocity>,
+    locomotions: WriteStorage<'a, Locomotion>,
+    routes: WriteStorage<'a, Route>,
     keyboard_event: Read<'a, Option<KeyboardEvent>>,
 }
 
@@ -17,21 +24,23 @@ impl<'a> System<'a> for Keyboard {
     type SystemData = KeyboardData<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let KeyboardData {players, mut velocities, keyboard_event} = data;
-        match *keyboard_event {
-            Some(MoveInDirection(direction)) => {
-                for (&Player {movement_speed}, velocity) in (&players, &mut velocities).join() {
-                    velocity.speed = movement_speed;
-                    velocity.direction = direction;
+        let KeyboardData {entities, players, mut locomotions, mut routes, keyboard_event} = data;
+        let heading = match *keyboard_event {
+            Some(MoveInDirection(direction)) => direction.into_vector(),
+            Some(Move {x, y}) => Vector::new(x, y),
+            Some(Stop) => Vector::ZERO,
+            Some(MoveTo {x, y}) => {
+                for (entity, _) in (&entities, &players).join() {
+                    routes.insert(entity, Route::new(Point::new(x, y)))
+                        .expect("failed to give the player a route");
                 }
+                return;
             },
-            Some(Stop) => {
-                for (_, velocity) in (&players, &mut velocities).join() {
-                    velocity.speed = 0;
-                }
-            },
-            Some(Escape) => {},
-            None => {},
+            None => return,
+        };
+        for (entity, _, locomotion) in (&entities, &players, &mut locomotions).join() {
+            locomotion.heading = heading;
+            routes.remove(entity);
         }
     }
-}
\ No newline at end of file
+}
@@ -1,17 +1,38 @@
 use sdl2::rect::Rect;
-use specs::{System, SystemData, ReadExpect, ReadStorage, WriteStorage, Join, World, prelude::ResourceId};
+use specs::{System, SystemData, Entity, Entities, Read, ReadExpect, ReadStorage, Write, WriteStorage, Join, World, prelude::ResourceId};
 
-use crate::resources::TimeDelta;
-use crate::components::{BoundingBox, Velocity};
+use crate::direction::Direction;
+use crate::tilemap::TileMap;
+use crate::resources::{TimeDelta, CollisionEvents, CollisionEvent, Obstacle};
+use crate::vector::Vector;
+use crate::components::{BoundingBox, Position, PreviousPosition, Velocity, Locomotion, Collider, Solid};
 
+/// Moves entities according to their `Velocity`, after steering the velocity of the ones with a
+/// `Locomotion` towards their heading. Entities are kept within the bounds of the world and
+/// `Collider` entities are stopped by `Solid` ones and by the solid tiles of the `TileMap`. Every
+/// collision is reported in the `CollisionEvents` resource.
+///
+/// Entities move by fractions of a pixel in their `Position`, and their `BoundingBox` follows
+/// the position rounded to whole pixels, so that slow entities still make progress when an
+/// update moves them by less than a pixel.
 pub struct Movement {
+    /// The area of the world entities are allowed to move in
     pub world_bounds: Rect,
 }
 
+/// The data read and written by the `Movement` system
 #[derive(SystemData)]
 pub struct MovementData<'a> {
-    velocities: ReadStorage<'a, Velocity>,
+    entities: Entities<'a>,
+    locomotions: ReadStorage<'a, Locomotion>,
+    velocities: WriteStorage<'a, Velocity>,
+    positions: WriteStorage<'a, Position>,
+    previous_positions: WriteStorage<'a, PreviousPosition>,
+    colliders: ReadStorage<'a, Collider>,
+    solids: ReadStorage<'a, Solid>,
     bounding_boxes: WriteStorage<'a, BoundingBox>,
+    collision_events: Write<'a, CollisionEvents>,
+    tile_map: Read<'a, TileMap>,
     time_delta: ReadExpect<'a, TimeDelta>,
 }
 
@@ -19,19 +40,131 @@ impl<'a> System<'a> for Movement {
     type SystemData = MovementData<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let MovementData {velocities, mut bounding_boxes, time_delta} = data;
+        let MovementData {
+            entities,
+            locomotions,
+            mut velocities,
+            mut positions,
+            mut previous_positions,
+            colliders,
+            solids,
+            mut bounding_boxes,
+            mut collision_events,
+            tile_map,
+            time_delta,
+        } = data;
         let TimeDelta(time_elapsed) = *time_delta;
+        let seconds = time_elapsed.as_secs_f32();
+        let CollisionEvents(collision_events) = &mut *collision_events;
+        collision_events.clear();
 
-        for (&Velocity {speed, direction}, BoundingBox(bounds)) in (&velocities, &mut bounding_boxes).join() {
-            if speed == 0 {
+        // Solid entities are static obstacles, so they can be collected once before anything moves
+        let obstacles: Vec<(Entity, Rect)> = (&entities, &solids, &bounding_boxes).join()
+            .map(|(entity, _, &BoundingBox(bounds))| (entity, bounds))
+            .collect();
+
+        for (entity, Velocity(velocity), Position(position), locomotion, collider, BoundingBox(bounds))
+            in (&entities, &mut velocities, &mut positions, locomotions.maybe(), colliders.maybe(), &mut bounding_boxes).join()
+        {
+            // Remember where the entity was so that rendering can interpolate up to the new position
+            previous_positions.insert(entity, PreviousPosition(*position))
+                .expect("failed to store previous position");
+            if let Some(locomotion) = locomotion {
+                *velocity = locomotion.steer(*velocity, seconds);
+            }
+            if *velocity == Vector::ZERO {
                 continue;
             }
-            let distance = speed * time_elapsed.as_micros() as i32 / 1_000_000;
-            let new_pos = bounds.center() + direction.into_point() * distance;
-            let new_bounds = Rect::from_center(new_pos, bounds.width(), bounds.height());
+            let movement = *velocity * seconds;
+            let (obstacles, tile_map): (&[(Entity, Rect)], _) = match collider {
+                Some(_) => (&obstacles, Some(&*tile_map)),
+                None => (&[], None),
+            };
+
+            // Each axis is moved and resolved separately so that an entity moving diagonally
+            // against a wall keeps sliding along it. An entity that runs into something stops
+            // along that axis, right against the obstacle.
+            let mut new_bounds = *bounds;
+            if movement.x != 0.0 {
+                position.x += movement.x;
+                new_bounds.set_x(position.x.round() as i32 - new_bounds.width() as i32 / 2);
+                let axis_direction = if movement.x > 0.0 { Direction::Right } else { Direction::Left };
+                if self.resolve(entity, &mut new_bounds, axis_direction, obstacles, tile_map, collision_events) {
+                    position.x = new_bounds.center().x() as f32;
+                    velocity.x = 0.0;
+                }
+            }
+            if movement.y != 0.0 {
+                position.y += movement.y;
+                new_bounds.set_y(position.y.round() as i32 - new_bounds.height() as i32 / 2);
+                let axis_direction = if movement.y > 0.0 { Direction::Down } else { Direction::Up };
+                if self.resolve(entity, &mut new_bounds, axis_direction, obstacles, tile_map, collision_events) {
+                    position.y = new_bounds.center().y() as f32;
+                    velocity.y = 0.0;
+                }
+            }
             *bounds = new_bounds;
-            /*if self.world_bounds.contains_rect(new_bounds) {
-            }*/
         }
     }
-}
\ No newline at end of file
+}
+
+impl Movement {
+    /// Pushes `bounds`, which just moved in `direction`, back out of the edges of the world, of
+    /// any obstacle and of any solid tile it now overlaps. Returns whether it ran into anything.
+    fn resolve(
+        &self,
+        entity: Entity,
+        bounds: &mut Rect,
+        direction: Direction,
+        obstacles: &[(Entity, Rect)],
+        tile_map: Option<&TileMap>,
+        collision_events: &mut Vec<CollisionEvent>,
+    ) -> bool {
+        let collisions = collision_events.len();
+        let world = self.world_bounds;
+        let out_of_world = match direction {
+            Direction::Left => bounds.left() < world.left(),
+            Direction::Right => bounds.right() > world.right(),
+            Direction::Up => bounds.top() < world.top(),
+            Direction::Down => bounds.bottom() > world.bottom(),
+        };
+        if out_of_world {
+            push_back(bounds, direction, world, true);
+            collision_events.push(CollisionEvent {entity, obstacle: Obstacle::WorldEdge, direction});
+        }
+
+        for &(obstacle, obstacle_bounds) in obstacles {
+            if obstacle != entity && bounds.has_intersection(obstacle_bounds) {
+                push_back(bounds, direction, obstacle_bounds, false);
+                collision_events.push(CollisionEvent {entity, obstacle: Obstacle::Entity(obstacle), direction});
+            }
+        }
+
+        if let Some(tile_map) = tile_map {
+            for (column, row, tile_bounds) in tile_map.solid_cells_overlapping(*bounds) {
+                // Being pushed out of one tile may already have moved the entity out of the next
+                if bounds.has_intersection(tile_bounds) {
+                    push_back(bounds, direction, tile_bounds, false);
+                    collision_events.push(CollisionEvent {entity, obstacle: Obstacle::Tile {column, row}, direction});
+                }
+            }
+        }
+        collision_events.len() > collisions
+    }
+}
+
+/// Moves `bounds` back against the side of `limit` it ran into while moving in `direction`.
+/// `inside` tells whether `bounds` must stay inside of `limit` (the world) or outside of it (an
+/// obstacle).
+fn push_back(bounds: &mut Rect, direction: Direction, limit: Rect, inside: bool) {
+    match (direction, inside) {
+        (Direction::Left, true) => bounds.set_x(limit.left()),
+        (Direction::Right, true) => bounds.set_right(limit.right()),
+        (Direction::Up, true) => bounds.set_y(limit.top()),
+        (Direction::Down, true) => bounds.set_bottom(limit.bottom()),
+        (Direction::Left, false) => bounds.set_x(limit.right()),
+        (Direction::Right, false) => bounds.set_right(limit.left()),
+        (Direction::Up, false) => bounds.set_y(limit.bottom()),
+        (Direction::Down, false) => bounds.set_bottom(limit.top()),
+    }
+}
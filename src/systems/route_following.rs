@@ -0,0 +1,78 @@
+use sdl2::rect::Point;
+use specs::{System, SystemData, Entities, ReadExpect, WriteExpect, ReadStorage, WriteStorage, Join, World, prelude::ResourceId};
+
+use crate::pathfinding::Pathfinder;
+use crate::vector::Vector;
+use crate::resources::TimeDelta;
+use crate::components::{BoundingBox, Position, Locomotion, Route};
+
+/// How close to a waypoint an entity must be to have reached it, in pixels
+const ARRIVAL_DISTANCE: f32 = 0.5;
+
+/// Plans the `Route` of every entity that has one and steers its `Locomotion` from one waypoint
+/// of the route to the next, slowing down before each of them so as to stop right on it rather
+/// than overshoot the turn. The route is removed and the entity stopped once the last waypoint
+/// is reached. Routes are planned with the `Pathfinder`.
+pub struct RouteFollowing;
+
+/// The data read and written by the `RouteFollowing` system
+#[derive(SystemData)]
+pub struct RouteFollowingData<'a> {
+    entities: Entities<'a>,
+    routes: WriteStorage<'a, Route>,
+    locomotions: WriteStorage<'a, Locomotion>,
+    positions: ReadStorage<'a, Position>,
+    bounding_boxes: ReadStorage<'a, BoundingBox>,
+    pathfinder: WriteExpect<'a, Pathfinder>,
+    time_delta: ReadExpect<'a, TimeDelta>,
+}
+
+impl<'a> System<'a> for RouteFollowing {
+    type SystemData = RouteFollowingData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let RouteFollowingData {
+            entities,
+            mut routes,
+            mut locomotions,
+            positions,
+            bounding_boxes,
+            mut pathfinder,
+            time_delta,
+        } = data;
+        let TimeDelta(time_elapsed) = *time_delta;
+        let seconds = time_elapsed.as_secs_f32();
+
+        let mut arrived = Vec::new();
+        for (entity, route, locomotion, &Position(position), &BoundingBox(bounds)) in (&entities, &mut routes, &mut locomotions, &positions, &bounding_boxes).join() {
+            let waypoints = route.waypoints.get_or_insert_with(|| {
+                pathfinder.find_path(position.to_point(), route.destination, (bounds.width(), bounds.height()))
+            });
+
+            let reached = |&waypoint: &Point| (Vector::from_point(waypoint) - position).length() <= ARRIVAL_DISTANCE;
+            let remaining = waypoints.iter().position(|waypoint| !reached(waypoint)).unwrap_or(waypoints.len());
+            waypoints.drain(..remaining);
+            let waypoint = match waypoints.first() {
+                Some(&waypoint) => Vector::from_point(waypoint),
+                None => {
+                    locomotion.heading = Vector::ZERO;
+                    arrived.push(entity);
+                    continue;
+                },
+            };
+
+            // Never faster than what is left to walk during this update, nor than the speed the
+            // entity can still brake from before the waypoint
+            let offset = waypoint - position;
+            let distance = offset.length();
+            let step = locomotion.max_speed * seconds;
+            let braking_speed = (2.0 * locomotion.acceleration * distance).sqrt();
+            let fraction = (distance / step).min(braking_speed / locomotion.max_speed).min(1.0);
+            locomotion.heading = offset.normalized() * fraction;
+        }
+
+        for entity in arrived {
+            routes.remove(entity);
+        }
+    }
+}
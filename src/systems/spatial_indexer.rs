@@ -0,0 +1,27 @@
+use specs::{System, SystemData, Entities, ReadStorage, Write, Join, World, prelude::ResourceId};
+
+use crate::spatial_grid::SpatialGrid;
+use crate::components::BoundingBox;
+
+/// Rebuilds the `SpatialGrid` from the `BoundingBox` of every entity. Runs once entities have moved
+/// so that the systems after it query up to date positions.
+pub struct SpatialIndexer;
+
+/// The data read and written by the `SpatialIndexer` system
+#[derive(SystemData)]
+pub struct SpatialIndexerData<'a> {
+    entities: Entities<'a>,
+    bounding_boxes: ReadStorage<'a, BoundingBox>,
+    spatial_grid: Write<'a, SpatialGrid>,
+}
+
+impl<'a> System<'a> for SpatialIndexer {
+    type SystemData = SpatialIndexerData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        let SpatialIndexerData {entities, bounding_boxes, mut spatial_grid} = data;
+
+        spatial_grid.rebuild((&entities, &bounding_boxes).join()
+            .map(|(entity, &BoundingBox(bounds))| (entity, bounds)));
+    }
+}
@@ -1,16 +1,22 @@
-use specs::{System, SystemData, ReadStorage, WriteExpect, Join, World, prelude::ResourceId};
+use specs::{System, SystemData, Read, ReadStorage, WriteStorage, WriteExpect, Join, World, prelude::ResourceId};
 
 use crate::resources::GameStatus;
-use crate::components::{Player, Enemy, Goal, BoundingBox};
+use crate::spatial_grid::SpatialGrid;
+use crate::components::{Player, Enemy, Goal, BoundingBox, AnimationStateMachine};
 
+/// Updates the `GameStatus` when the player touches an enemy or the goal, and has the player
+/// play their death or victory animation
 pub struct WinLoseChecker;
 
+/// The data read and written by the `WinLoseChecker` system
 #[derive(SystemData)]
 pub struct WinLoseCheckerData<'a> {
     players: ReadStorage<'a, Player>,
     enemies: ReadStorage<'a, Enemy>,
     goals: ReadStorage<'a, Goal>,
     bounding_boxes: ReadStorage<'a, BoundingBox>,
+    animation_state_machines: WriteStorage<'a, AnimationStateMachine>,
+    spatial_grid: Read<'a, SpatialGrid>,
     game_status: WriteExpect<'a, GameStatus>,
 }
 
@@ -18,21 +24,33 @@ impl<'a> System<'a> for WinLoseChecker {
     type SystemData = WinLoseCheckerData<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let WinLoseCheckerData {players, enemies, goals, bounding_boxes, mut game_status} = data;
+        let WinLoseCheckerData {
+            players,
+            enemies,
+            goals,
+            bounding_boxes,
+            mut animation_state_machines,
+            spatial_grid,
+            mut game_status,
+        } = data;
 
-        for (_, BoundingBox(player_bounds)) in (&players, &bounding_boxes).join() {
-            for (_, &BoundingBox(enemy_bounds)) in (&enemies, &bounding_boxes).join() {
-                if player_bounds.has_intersection(enemy_bounds) {
-                    *game_status = GameStatus::Lose;
-                    return;
+        for (_, &BoundingBox(player_bounds), state_machine) in (&players, &bounding_boxes, (&mut animation_state_machines).maybe()).join() {
+            let touching = spatial_grid.query_rect(player_bounds);
+            // Touching an enemy loses the game even when the goal is reached at the same time
+            if touching.iter().any(|&entity| enemies.contains(entity)) {
+                *game_status = GameStatus::Lose;
+                if let Some(state_machine) = state_machine {
+                    state_machine.flags.dead = true;
                 }
+                return;
             }
-            for (_, &BoundingBox(goal_bounds)) in (&goals, &bounding_boxes).join() {
-                if player_bounds.has_intersection(goal_bounds) {
-                    *game_status = GameStatus::Win;
-                    return;
+            if touching.iter().any(|&entity| goals.contains(entity)) {
+                *game_status = GameStatus::Win;
+                if let Some(state_machine) = state_machine {
+                    state_machine.flags.won = true;
                 }
+                return;
             }
         }
     }
-}
\ No newline at end of file
+}
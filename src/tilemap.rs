@@ -0,0 +1,395 @@
+//! A grid of tiles drawn beneath every entity. Maps are authored with the Tiled editor and
+//! exported to its JSON format (https://doc.mapeditor.org/en/stable/reference/json-map-format/).
+//! Only orthogonal maps with tile layers and a single embedded tileset are supported.
+//!
+//! Tiles whose tileset entry has a boolean `solid` property set to `true` block `Collider`
+//! entities, just like `Solid` entities do.
+
+use std::fmt;
+use std::fs;
+use std::error::Error;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use sdl2::rect::{Point, Rect};
+use serde::Deserialize;
+
+use crate::assets::TextureHandle;
+
+/// The bits of a Tiled global tile id used to flip tiles, which are not supported
+const TILED_FLIP_FLAGS: u32 = 0xF000_0000;
+
+/// A tile map placed in the world. The default map is empty, for levels without a map.
+#[derive(Debug, Clone)]
+pub struct TileMap {
+    /// The position of the top-left corner of the map in world coordinates
+    pub origin: Point,
+    /// The number of tiles in each row of the map
+    pub columns: u32,
+    /// The number of rows of the map
+    pub rows: u32,
+    /// The width and height of every tile in world coordinates
+    pub tile_size: (u32, u32),
+    /// The layers of the map from bottom to top. Each layer holds one tile per cell of the map,
+    /// row by row, as an index in the tileset or `None` for an empty cell.
+    pub layers: Vec<Vec<Option<u32>>>,
+    /// The tileset the tiles of the map are taken from
+    pub tileset: Tileset,
+    /// Whether each cell of the map, row by row, contains a solid tile in any layer
+    solid_cells: Vec<bool>,
+}
+
+/// The image the tiles of a map are taken from
+#[derive(Debug, Clone, Default)]
+pub struct Tileset {
+    /// The path of the image
+    pub image: PathBuf,
+    /// The texture of the image, once the map has been spawned in a world and the image has been
+    /// registered in its `AssetManager`
+    pub texture: Option<TextureHandle>,
+    /// The number of tiles in each row of the image
+    pub columns: u32,
+    /// The width and height of every tile in the image, in pixels
+    pub tile_size: (u32, u32),
+    /// The indices of the tiles that block movement
+    pub solid_tiles: HashSet<u32>,
+}
+
+/// The reasons a tile map can fail to load
+#[derive(Debug)]
+pub enum TileMapError {
+    /// The map file could not be read
+    Io {
+        /// The path of the map file
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// The map file is not valid JSON or is not a Tiled map
+    Parse {
+        /// The path of the map file
+        path: PathBuf,
+        /// The underlying error, including the position of the error in the file
+        source: serde_json::Error,
+    },
+    /// The map file is a Tiled map that cannot be used by this game
+    Unsupported {
+        /// The path of the map file
+        path: PathBuf,
+        /// What is wrong with the map
+        message: String,
+    },
+}
+
+impl fmt::Display for TileMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TileMapError::Io {path, source} => write!(f, "Could not read tile map {}: {}", path.display(), source),
+            TileMapError::Parse {path, source} => write!(f, "Could not parse tile map {}: {}", path.display(), source),
+            TileMapError::Unsupported {path, message} => write!(f, "Unsupported tile map {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for TileMapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TileMapError::Io {source, ..} => Some(source),
+            TileMapError::Parse {source, ..} => Some(source),
+            TileMapError::Unsupported {..} => None,
+        }
+    }
+}
+
+/// The subset of the Tiled JSON map format used by this game
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+struct TiledMap {
+    orientation: String,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    infinite: bool,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default = "visible_by_default")]
+    visible: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    /// Missing for tilesets stored in a separate file
+    image: Option<PathBuf>,
+    #[serde(default)]
+    columns: u32,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    tiles: Vec<TiledTile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledTile {
+    id: u32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+fn visible_by_default() -> bool {
+    true
+}
+
+impl Default for TileMap {
+    fn default() -> Self {
+        Self {
+            origin: Point::new(0, 0),
+            columns: 0,
+            rows: 0,
+            tile_size: (0, 0),
+            layers: Vec::new(),
+            tileset: Tileset::default(),
+            solid_cells: Vec::new(),
+        }
+    }
+}
+
+impl TileMap {
+    /// Reads a map exported by Tiled in JSON. The top-left corner of the map is placed at
+    /// `origin` in the world.
+    pub fn load_tiled_json(path: impl AsRef<Path>, origin: Point) -> Result<Self, TileMapError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|source| TileMapError::Io {path: path.to_path_buf(), source})?;
+        Self::parse_tiled_json(&source, path, origin)
+    }
+
+    /// Parses a map exported by Tiled in JSON. The `path` is used in error messages and to find
+    /// the tileset image, which Tiled stores relative to the map file.
+    pub fn parse_tiled_json(source: &str, path: impl AsRef<Path>, origin: Point) -> Result<Self, TileMapError> {
+        let path = path.as_ref();
+        let map: TiledMap = serde_json::from_str(source)
+            .map_err(|source| TileMapError::Parse {path: path.to_path_buf(), source})?;
+        let unsupported = |message: String| TileMapError::Unsupported {path: path.to_path_buf(), message};
+
+        if map.orientation != "orthogonal" || map.infinite {
+            return Err(unsupported(format!("only finite orthogonal maps are supported, got {} map", map.orientation)));
+        }
+        let tiled_tileset = match map.tilesets.as_slice() {
+            [tileset] => tileset,
+            tilesets => return Err(unsupported(format!("expected exactly one tileset, got {}", tilesets.len()))),
+        };
+        let image = tiled_tileset.image.as_ref()
+            .ok_or_else(|| unsupported("the tileset must be embedded in the map".to_string()))?;
+        if tiled_tileset.columns == 0 || tiled_tileset.tilewidth == 0 || tiled_tileset.tileheight == 0 {
+            return Err(unsupported("the tileset must have a non-empty tile size and at least one column".to_string()));
+        }
+        let solid_tiles = tiled_tileset.tiles.iter()
+            .filter(|tile| tile.properties.iter().any(|property| property.name == "solid" && property.value == true))
+            .map(|tile| tile.id)
+            .collect();
+        let tileset = Tileset {
+            // Tiled stores paths relative to the map file
+            image: path.parent().unwrap_or_else(|| Path::new("")).join(image),
+            texture: None,
+            columns: tiled_tileset.columns,
+            tile_size: (tiled_tileset.tilewidth, tiled_tileset.tileheight),
+            solid_tiles,
+        };
+
+        // Checked in `u32` so that the index of every cell fits in a `u32` too
+        let cell_count = map.width.checked_mul(map.height)
+            .ok_or_else(|| unsupported(format!("the map is too large, got {}x{} tiles", map.width, map.height)))? as usize;
+        let mut layers = Vec::new();
+        for (i, layer) in map.layers.iter().enumerate() {
+            if layer.kind != "tilelayer" || !layer.visible {
+                continue;
+            }
+            if layer.data.len() != cell_count {
+                return Err(unsupported(format!("layer #{} has {} tiles instead of {}", i, layer.data.len(), cell_count)));
+            }
+            let tiles = layer.data.iter()
+                .map(|&gid| match gid {
+                    gid if gid & TILED_FLIP_FLAGS != 0 => Err(unsupported(format!("layer #{} flips tile {}, which is not supported", i, gid & !TILED_FLIP_FLAGS))),
+                    0 => Ok(None),
+                    gid if gid >= tiled_tileset.firstgid => Ok(Some(gid - tiled_tileset.firstgid)),
+                    gid => Err(unsupported(format!("layer #{} uses unknown tile {}", i, gid))),
+                })
+                .collect::<Result<_, _>>()?;
+            layers.push(tiles);
+        }
+
+        let mut tile_map = Self {
+            origin,
+            columns: map.width,
+            rows: map.height,
+            tile_size: (map.tilewidth, map.tileheight),
+            layers,
+            tileset,
+            solid_cells: Vec::new(),
+        };
+        tile_map.solid_cells = (0..cell_count)
+            .map(|cell| tile_map.layers.iter().any(|layer| matches!(layer[cell], Some(tile) if tile_map.tileset.solid_tiles.contains(&tile))))
+            .collect();
+        Ok(tile_map)
+    }
+
+    /// The area of the world covered by the cell at the given column and row
+    pub fn cell_bounds(&self, column: u32, row: u32) -> Rect {
+        let (tile_width, tile_height) = self.tile_size;
+        Rect::new(
+            self.origin.x() + (column * tile_width) as i32,
+            self.origin.y() + (row * tile_height) as i32,
+            tile_width,
+            tile_height,
+        )
+    }
+
+    /// The region of the tileset image to draw for the given tile
+    pub fn tile_region(&self, tile: u32) -> Rect {
+        let Tileset {columns, tile_size: (tile_width, tile_height), ..} = self.tileset;
+        Rect::new(
+            ((tile % columns) * tile_width) as i32,
+            ((tile / columns) * tile_height) as i32,
+            tile_width,
+            tile_height,
+        )
+    }
+
+    /// The columns and rows of the cells overlapping the given area of the world, clipped to the
+    /// map, as `(first_column, first_row)` and `(last_column, last_row)` inclusive
+    pub fn cells_overlapping(&self, area: Rect) -> Option<((u32, u32), (u32, u32))> {
+        let (tile_width, tile_height) = (self.tile_size.0 as i32, self.tile_size.1 as i32);
+        if self.columns == 0 || self.rows == 0 || tile_width == 0 || tile_height == 0 {
+            return None;
+        }
+        let left = (area.left() - self.origin.x()).div_euclid(tile_width);
+        let top = (area.top() - self.origin.y()).div_euclid(tile_height);
+        // The right and bottom edges are exclusive
+        let right = (area.right() - 1 - self.origin.x()).div_euclid(tile_width);
+        let bottom = (area.bottom() - 1 - self.origin.y()).div_euclid(tile_height);
+        if right < 0 || bottom < 0 || left >= self.columns as i32 || top >= self.rows as i32 {
+            return None;
+        }
+        Some((
+            (left.max(0) as u32, top.max(0) as u32),
+            (right.min(self.columns as i32 - 1) as u32, bottom.min(self.rows as i32 - 1) as u32),
+        ))
+    }
+
+    /// Whether the cell at the given column and row contains a solid tile
+    pub fn is_solid(&self, column: u32, row: u32) -> bool {
+        column < self.columns && row < self.rows && self.solid_cells[(row * self.columns + column) as usize]
+    }
+
+    /// The columns, rows and bounds of the solid cells overlapping the given area of the world
+    pub fn solid_cells_overlapping(&self, area: Rect) -> Vec<(u32, u32, Rect)> {
+        let mut cells = Vec::new();
+        if let Some(((first_column, first_row), (last_column, last_row))) = self.cells_overlapping(area) {
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    if self.is_solid(column, row) {
+                        cells.push((column, row, self.cell_bounds(column, row)));
+                    }
+                }
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Tiled map of 3x2 tiles of 16x16 pixels with a single layer holding the given global tile
+    /// ids. The tileset starts at global id 1, and its tile 1 is solid.
+    fn tiled_json(data: &[u32]) -> String {
+        format!(r#"{{
+            "orientation": "orthogonal", "width": 3, "height": 2, "tilewidth": 16, "tileheight": 16,
+            "layers": [{{"type": "tilelayer", "data": {:?}}}],
+            "tilesets": [{{
+                "firstgid": 1, "image": "tiles.png", "columns": 4, "tilewidth": 16, "tileheight": 16,
+                "tiles": [{{"id": 1, "properties": [{{"name": "solid", "type": "bool", "value": true}}]}}]
+            }}]
+        }}"#, data)
+    }
+
+    fn parse(source: &str, origin: Point) -> Result<TileMap, TileMapError> {
+        TileMap::parse_tiled_json(source, "maps/test.json", origin)
+    }
+
+    fn assert_unsupported(source: &str, expected: &str) {
+        match parse(source, Point::new(0, 0)) {
+            Err(TileMapError::Unsupported {message, ..}) => assert!(message.contains(expected), "{}", message),
+            other => panic!("expected an unsupported map, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_reads_tiles_and_solid_cells() {
+        let map = parse(&tiled_json(&[0, 1, 2, 2, 0, 5]), Point::new(0, 0)).unwrap();
+        assert_eq!((map.columns, map.rows), (3, 2));
+        assert_eq!(map.layers, vec![vec![None, Some(0), Some(1), Some(1), None, Some(4)]]);
+        assert_eq!(map.tileset.image, Path::new("maps/tiles.png"));
+        assert!(map.is_solid(2, 0) && map.is_solid(0, 1));
+        assert!(!map.is_solid(1, 0) && !map.is_solid(3, 0));
+    }
+
+    #[test]
+    fn parse_rejects_flipped_and_unknown_tiles() {
+        assert_unsupported(&tiled_json(&[0, 0, 0x8000_0002, 0, 0, 0]), "flips tile 2");
+        let before_the_tileset = tiled_json(&[0, 1, 2, 2, 0, 5]).replace(r#""firstgid": 1"#, r#""firstgid": 3"#);
+        assert_unsupported(&before_the_tileset, "unknown tile 1");
+    }
+
+    #[test]
+    fn parse_rejects_layers_of_the_wrong_length() {
+        assert_unsupported(&tiled_json(&[0, 1, 2, 2, 0]), "has 5 tiles instead of 6");
+        assert_unsupported(&tiled_json(&[0, 1, 2, 2, 0, 5, 5]), "has 7 tiles instead of 6");
+        let huge = tiled_json(&[]).replace(r#""width": 3, "height": 2"#, r#""width": 65536, "height": 65536"#);
+        assert_unsupported(&huge, "too large");
+    }
+
+    #[test]
+    fn cells_overlapping_are_clipped_to_the_map() {
+        let map = parse(&tiled_json(&[0; 6]), Point::new(0, 0)).unwrap();
+        assert_eq!(map.cells_overlapping(Rect::new(0, 0, 16, 16)), Some(((0, 0), (0, 0))));
+        assert_eq!(map.cells_overlapping(Rect::new(10, 10, 10, 10)), Some(((0, 0), (1, 1))));
+        assert_eq!(map.cells_overlapping(Rect::new(-100, -100, 1000, 1000)), Some(((0, 0), (2, 1))));
+        assert_eq!(map.cells_overlapping(Rect::new(40, 20, 100, 100)), Some(((2, 1), (2, 1))));
+        // Touching the map from outside does not overlap it
+        assert_eq!(map.cells_overlapping(Rect::new(-16, 0, 16, 16)), None);
+        assert_eq!(map.cells_overlapping(Rect::new(48, 0, 16, 16)), None);
+        assert_eq!(map.cells_overlapping(Rect::new(0, 32, 16, 16)), None);
+    }
+
+    #[test]
+    fn cells_overlapping_a_map_with_a_negative_origin() {
+        let map = parse(&tiled_json(&[0, 1, 2, 2, 0, 5]), Point::new(-24, -16)).unwrap();
+        assert_eq!(map.cell_bounds(0, 0), Rect::new(-24, -16, 16, 16));
+        assert_eq!(map.cells_overlapping(Rect::new(-1, -1, 2, 2)), Some(((1, 0), (1, 1))));
+        assert_eq!(map.cells_overlapping(Rect::new(-30, -20, 10, 10)), Some(((0, 0), (0, 0))));
+        assert_eq!(map.cells_overlapping(Rect::new(-40, -40, 16, 24)), None);
+        let solid: Vec<_> = map.solid_cells_overlapping(Rect::new(-24, -16, 48, 32)).into_iter().map(|(column, row, _)| (column, row)).collect();
+        assert_eq!(solid, vec![(2, 0), (0, 1)]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Two dimensional vectors of floats, used to move entities by fractions of a pixel. Positions on
+//! the screen and bounding boxes stay in whole pixels, see `Vector::to_point`.
+
+use std::ops::{Add, AddAssign, Sub, Mul};
+
+use sdl2::rect::Point;
+use serde::{Serialize, Deserialize};
+
+/// A position, a movement or a velocity in world coordinates
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Vector {
+    /// The horizontal component, growing towards the right of the screen
+    pub x: f32,
+    /// The vertical component, growing towards the bottom of the screen
+    pub y: f32,
+}
+
+impl Vector {
+    /// The vector of length zero
+    pub const ZERO: Vector = Vector {x: 0.0, y: 0.0};
+
+    /// Creates a vector from its components
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self {x, y}
+    }
+
+    /// The position of the given point
+    pub fn from_point(point: Point) -> Self {
+        Self::new(point.x() as f32, point.y() as f32)
+    }
+
+    /// The point closest to this position, in whole pixels
+    pub fn to_point(self) -> Point {
+        Point::new(self.x.round() as i32, self.y.round() as i32)
+    }
+
+    /// The length of the vector
+    pub fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    /// The vector pointing the same way with a length of one, or the zero vector if this one has
+    /// no direction
+    pub fn normalized(self) -> Self {
+        let length = self.length();
+        if length == 0.0 { Self::ZERO } else { self * (1.0 / length) }
+    }
+
+    /// The vector pointing the same way, shortened to `max_length` if it is longer than that
+    pub fn clamp_length(self, max_length: f32) -> Self {
+        let length = self.length();
+        if length <= max_length { self } else { self * (max_length / length) }
+    }
+
+    /// The vector moved towards `target` by at most `max_distance`, without overshooting it. A
+    /// `max_distance` that is not finite, e.g. an infinite rate times no time at all, reaches the
+    /// target at once.
+    pub fn move_towards(self, target: Self, max_distance: f32) -> Self {
+        let offset = target - self;
+        let distance = offset.length();
+        if !max_distance.is_finite() || distance <= max_distance { target } else { self + offset * (max_distance / distance) }
+    }
+}
+
+impl Add for Vector {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vector {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vector {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f32> for Vector {
+    type Output = Self;
+
+    fn mul(self, factor: f32) -> Self {
+        Self::new(self.x * factor, self.y * factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_towards_stops_at_the_target() {
+        let start = Vector::new(0.0, 0.0);
+        let target = Vector::new(3.0, 4.0);
+        assert_eq!(start.move_towards(target, 2.5), Vector::new(1.5, 2.0));
+        assert_eq!(start.move_towards(target, 10.0), target);
+    }
+
+    #[test]
+    fn move_towards_snaps_to_the_target_without_a_finite_distance() {
+        let start = Vector::new(1.0, 1.0);
+        let target = Vector::new(-2.0, 5.0);
+        assert_eq!(start.move_towards(target, f32::INFINITY), target);
+        assert_eq!(start.move_towards(target, f32::INFINITY * 0.0), target);
+    }
+}
@@ -0,0 +1,75 @@
+//! Plays whole games without a window, from the spawning of a level to its end
+
+use learning_game_in_rust::game::{Game, GameBuilder};
+use learning_game_in_rust::level::Level;
+use learning_game_in_rust::resources::GameStatus;
+
+/// The player starts at the bottom of the arena and the goal is at the top. A guard stands on the
+/// right of the way and chases the player once they come within its sight.
+const ARENA: &str = r#"
+Level(
+    name: "Arena",
+    world_size: (800, 800),
+    sprite_sheets: [
+        (name: "bardo", path: "assets/bardo_2x.png"),
+        (name: "reaper", path: "assets/reaper_blade_2x.png", animations: Some("assets/sprites/reaper.ron")),
+        (name: "pink_trees", path: "assets/pinktrees_2x.png"),
+    ],
+    player: (
+        position: Fixed(0, 250),
+        size: (32, 58),
+        movement_speed: 200,
+        animations: Standard(sheet: "bardo", top_left_frame: (0, 0, 52, 72), frames_length: 3, step_delay_ms: 150),
+    ),
+    goal: (
+        position: Fixed(0, -250),
+        size: (92, 116),
+        sprite: (sheet: "pink_trees", region: (0, 0, 128, 128)),
+    ),
+    enemies: [
+        (
+            position: Fixed(250, 0),
+            direction: Fixed(Left),
+            size: (50, 58),
+            speed: 150,
+            direction_change_delay_ms: 200,
+            idle: Guard,
+            on_sight: Chase,
+            sight_radius: 200,
+            animations: Named(sheet: "reaper", walking_up: "walk_up", walking_down: "walk_down", walking_left: "walk_left", walking_right: "walk_right"),
+        ),
+    ],
+)
+"#;
+
+/// Enough ticks to cross the arena a few times
+const TICKS: u64 = 600;
+
+fn arena() -> Game {
+    let level = Level::parse(ARENA, "arena.ron").unwrap();
+    GameBuilder::new(level.world_bounds())
+        .with_seed(7)
+        .with_level(level)
+        .build()
+}
+
+#[test]
+fn walking_to_the_goal_out_of_sight_of_the_guard_wins() {
+    let mut game = arena();
+    assert_eq!(game.run(TICKS, &"0:to 0 -250".parse().unwrap()), GameStatus::Win);
+    assert!(game.tick() < TICKS);
+}
+
+#[test]
+fn walking_up_to_the_guard_loses() {
+    let mut game = arena();
+    assert_eq!(game.run(TICKS, &"0:to 200 100".parse().unwrap()), GameStatus::Lose);
+    assert!(game.tick() < TICKS);
+}
+
+#[test]
+fn standing_still_keeps_the_game_running() {
+    let mut game = arena();
+    assert_eq!(game.run(TICKS, &"".parse().unwrap()), GameStatus::Running);
+    assert_eq!(game.tick(), TICKS);
+}